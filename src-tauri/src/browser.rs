@@ -0,0 +1,146 @@
+use std::io;
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+
+use crate::models::DetectedBrowser;
+
+// The names and executable names of the Chromium-family browsers we
+// look for in the PATH on Linux.
+#[cfg(target_os = "linux")]
+const LINUX_BROWSERS: [(&str, &str); 8] = [
+    ("Google Chrome", "google-chrome"),
+    ("Google Chrome", "google-chrome-stable"),
+    ("Chromium", "chromium"),
+    ("Chromium", "chromium-browser"),
+    ("Microsoft Edge", "microsoft-edge"),
+    ("Microsoft Edge", "microsoft-edge-stable"),
+    ("Brave", "brave-browser"),
+    ("Brave", "brave"),
+];
+
+// The names and paths of the Chromium-family browsers we look for on macOS.
+#[cfg(target_os = "macos")]
+const MACOS_BROWSERS: [(&str, &str); 4] = [
+    (
+        "Google Chrome",
+        "/Applications/Google Chrome.app/Contents/MacOS/Google Chrome",
+    ),
+    (
+        "Chromium",
+        "/Applications/Chromium.app/Contents/MacOS/Chromium",
+    ),
+    (
+        "Microsoft Edge",
+        "/Applications/Microsoft Edge.app/Contents/MacOS/Microsoft Edge",
+    ),
+    (
+        "Brave",
+        "/Applications/Brave Browser.app/Contents/MacOS/Brave Browser",
+    ),
+];
+
+// The names and paths, relative to the program files directories, of the
+// Chromium-family browsers we look for on Windows.
+#[cfg(target_os = "windows")]
+const WINDOWS_BROWSERS: [(&str, &str); 4] = [
+    ("Google Chrome", "Google\\Chrome\\Application\\chrome.exe"),
+    ("Chromium", "Chromium\\Application\\chrome.exe"),
+    ("Microsoft Edge", "Microsoft\\Edge\\Application\\msedge.exe"),
+    (
+        "Brave",
+        "BraveSoftware\\Brave-Browser\\Application\\brave.exe",
+    ),
+];
+
+// Returns the Chromium-family browsers installed on the machine, which
+// the recorder can drive.
+pub fn detect_browsers() -> Vec<DetectedBrowser> {
+    let mut browsers: Vec<DetectedBrowser> = vec![];
+
+    for (name, path) in candidate_browsers() {
+        let already_detected = browsers
+            .iter()
+            .any(|browser| browser.name == name || browser.path == path);
+        if path.is_file() && !already_detected {
+            browsers.push(DetectedBrowser {
+                name: name.to_string(),
+                path,
+            });
+        }
+    }
+
+    browsers
+}
+
+#[cfg(target_os = "linux")]
+fn candidate_browsers() -> Vec<(&'static str, PathBuf)> {
+    let paths: Vec<PathBuf> = std::env::var_os("PATH")
+        .map(|path| std::env::split_paths(&path).collect())
+        .unwrap_or_default();
+
+    LINUX_BROWSERS
+        .iter()
+        .flat_map(|(name, executable)| {
+            paths
+                .iter()
+                .map(move |directory| (*name, directory.join(executable)))
+        })
+        .collect()
+}
+
+#[cfg(target_os = "macos")]
+fn candidate_browsers() -> Vec<(&'static str, PathBuf)> {
+    MACOS_BROWSERS
+        .iter()
+        .map(|(name, path)| (*name, PathBuf::from(path)))
+        .collect()
+}
+
+#[cfg(target_os = "windows")]
+fn candidate_browsers() -> Vec<(&'static str, PathBuf)> {
+    let directories: Vec<PathBuf> = ["ProgramFiles", "ProgramFiles(x86)", "LOCALAPPDATA"]
+        .iter()
+        .filter_map(|variable| std::env::var_os(variable).map(PathBuf::from))
+        .collect();
+
+    WINDOWS_BROWSERS
+        .iter()
+        .flat_map(|(name, path)| {
+            directories
+                .iter()
+                .map(move |directory| (*name, directory.join(path)))
+        })
+        .collect()
+}
+
+// Returns the path of the browser the recorder should launch.
+//
+// A custom path takes precedence, and must point to an existing executable.
+// Otherwise the first detected browser is used, falling back to the one
+// headless_chrome finds.
+pub fn browser_path(custom_path: Option<&str>) -> io::Result<PathBuf> {
+    if let Some(custom_path) = custom_path {
+        let path = Path::new(custom_path);
+        if !path.is_file() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("browser not found at {}", custom_path),
+            ));
+        }
+
+        return Ok(path.to_path_buf());
+    }
+
+    if let Some(browser) = detect_browsers().into_iter().next() {
+        return Ok(browser.path);
+    }
+
+    headless_chrome::browser::default_executable()
+        .map_err(|e| io::Error::new(io::ErrorKind::NotFound, e))
+}
+
+// Returns a local TCP port that is currently free.
+pub fn free_port() -> io::Result<u16> {
+    let listener = TcpListener::bind(("127.0.0.1", 0))?;
+    Ok(listener.local_addr()?.port())
+}
@@ -0,0 +1,118 @@
+use std::path::{Path, PathBuf};
+use std::{fs, io};
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chrono::{Datelike, Utc};
+use rcgen::{
+    BasicConstraints, CertificateParams, DistinguishedName, DnType, IsCa, KeyPair, KeyUsagePurpose,
+};
+use sha2::{Digest, Sha256};
+
+use crate::storage;
+
+// The files mitmproxy looks for in its configuration directory: the private
+// key of the CA along with its certificate, and the certificate alone.
+const CA_FILE: &str = "mitmproxy-ca.pem";
+const CA_CERT_FILE: &str = "mitmproxy-ca-cert.pem";
+
+const CA_COMMON_NAME: &str = "kroco6 recorder CA";
+const CA_ORGANIZATION: &str = "kroco6";
+
+// How long the CA is valid for, in years
+const CA_VALIDITY_YEARS: i32 = 10;
+
+// CertificateAuthority is the CA used by the recorder's proxy to intercept
+// HTTPS traffic.
+//
+// Each install generates its own CA, whose private key never leaves the
+// machine. The browser launched by the recorder trusts it through the
+// fingerprint of its public key, rather than by installing it system-wide.
+pub struct CertificateAuthority {
+    // The directory holding the CA, to be used as mitmproxy's `confdir`
+    pub directory: PathBuf,
+
+    // The base64 encoded SHA-256 hash of the CA's SubjectPublicKeyInfo, as
+    // expected by chromium's `--ignore-certificate-errors-spki-list`
+    pub spki_fingerprint: String,
+}
+
+impl CertificateAuthority {
+    // Loads the CA from the given directory, generating it first if it
+    // doesn't exist yet or can't be read.
+    pub fn load_or_generate(directory: &Path) -> io::Result<Self> {
+        let key_pair = match load_key_pair(directory) {
+            Ok(key_pair) => key_pair,
+            Err(e) => {
+                if e.kind() != io::ErrorKind::NotFound {
+                    eprintln!("regenerating the recorder's certificate authority: {}", e);
+                }
+                generate(directory)?
+            }
+        };
+
+        Ok(Self {
+            directory: directory.to_path_buf(),
+            spki_fingerprint: BASE64.encode(Sha256::digest(key_pair.public_key_der())),
+        })
+    }
+}
+
+// Loads the private key of the CA stored in the directory
+fn load_key_pair(directory: &Path) -> io::Result<KeyPair> {
+    // the certificate is checked for as well, as mitmproxy needs both files
+    if !directory.join(CA_CERT_FILE).is_file() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            "certificate authority not found",
+        ));
+    }
+
+    // the key comes first in the file, which is all KeyPair::from_pem reads
+    let pem = fs::read_to_string(directory.join(CA_FILE))?;
+    KeyPair::from_pem(&pem).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+// Generates a new CA in the directory, replacing any previous one along
+// with the files mitmproxy derived from it.
+fn generate(directory: &Path) -> io::Result<KeyPair> {
+    if directory.exists() {
+        fs::remove_dir_all(directory)?;
+    }
+    fs::create_dir_all(directory)?;
+
+    let key_pair = KeyPair::generate().map_err(io::Error::other)?;
+
+    let mut distinguished_name = DistinguishedName::new();
+    distinguished_name.push(DnType::CommonName, CA_COMMON_NAME);
+    distinguished_name.push(DnType::OrganizationName, CA_ORGANIZATION);
+
+    let today = Utc::now();
+    let mut params = CertificateParams::default();
+    params.distinguished_name = distinguished_name;
+    params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+    params.key_usages = vec![
+        KeyUsagePurpose::KeyCertSign,
+        KeyUsagePurpose::CrlSign,
+        KeyUsagePurpose::DigitalSignature,
+    ];
+    params.not_before = rcgen::date_time_ymd(today.year(), today.month() as u8, today.day() as u8);
+    params.not_after = rcgen::date_time_ymd(
+        today.year() + CA_VALIDITY_YEARS,
+        today.month() as u8,
+        // avoid landing on a 29th of February which doesn't exist
+        today.day().min(28) as u8,
+    );
+
+    let certificate = params.self_signed(&key_pair).map_err(io::Error::other)?;
+
+    let ca_path = directory.join(CA_FILE);
+    storage::write_atomic_private(
+        &ca_path,
+        format!("{}{}", key_pair.serialize_pem(), certificate.pem()),
+    )?;
+
+    storage::write_atomic(&directory.join(CA_CERT_FILE), certificate.pem())?;
+
+    Ok(key_pair)
+}
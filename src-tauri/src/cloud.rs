@@ -1,4 +1,41 @@
-use crate::models::{CloudTest, CloudTestListResponse};
+use std::io;
+
+use reqwest::StatusCode;
+
+use crate::models::{
+    CloudConfigValidation, CloudProject, CloudTest, CloudTestListResponse, CloudTestRun,
+    CloudTestRunResponse, ProjectConfig,
+};
+use crate::secrets::SecretStore;
+
+// The key under which the app-wide cloud token is stored in the secret store.
+pub const CLOUD_TOKEN_SECRET_KEY: &str = "cloud_token";
+
+// Resolves the cloud token to use, in order of precedence:
+//   1. the project's own token, if a project config is provided and holds one
+//   2. the app-wide token, as stored in the secret store
+//   3. the K6_CLOUD_TOKEN environment variable
+//
+// Every interaction with the cloud should resolve its token through this
+// function, so that they all agree on which one to use.
+pub fn resolve_cloud_token(
+    project_config: Option<&ProjectConfig>,
+    secret_store: &dyn SecretStore,
+) -> io::Result<Option<String>> {
+    let project_token = project_config.and_then(|config| config.cloud_token.clone());
+    if let Some(token) = project_token.filter(|token| !token.is_empty()) {
+        return Ok(Some(token));
+    }
+
+    let app_token = secret_store.get(CLOUD_TOKEN_SECRET_KEY)?;
+    if let Some(token) = app_token.filter(|token| !token.is_empty()) {
+        return Ok(Some(token));
+    }
+
+    Ok(std::env::var("K6_CLOUD_TOKEN")
+        .ok()
+        .filter(|token| !token.is_empty()))
+}
 
 fn get_request(url: &str, cloud_token: &str) -> reqwest::RequestBuilder {
 
@@ -10,6 +47,14 @@ fn get_request(url: &str, cloud_token: &str) -> reqwest::RequestBuilder {
         .header(reqwest::header::USER_AGENT, "kroco6")
 }
 
+fn post_request(url: &str, cloud_token: &str) -> reqwest::RequestBuilder {
+    let client = reqwest::Client::new();
+    let url = format!("https://api.k6.io{}", url);
+    client.post(url)
+        .header(reqwest::header::AUTHORIZATION, format!("Token {}", cloud_token))
+        .header(reqwest::header::USER_AGENT, "kroco6")
+}
+
 pub async fn get_cloud_tests(cloud_token: &str, cloud_project_id: &str) -> Result<Vec<CloudTest>, String> {
     let tests_url = format!("/loadtests/v2/tests?$select=id,name,project_id,script&project_id={cloud_project_id}");
 
@@ -31,3 +76,120 @@ pub async fn get_cloud_tests(cloud_token: &str, cloud_project_id: &str) -> Resul
 
     Ok(cloud_test_list)
 }
+
+pub async fn get_test_run(cloud_token: &str, test_run_id: u32) -> Result<CloudTestRun, String> {
+    let test_run_url = format!("/loadtests/v2/runs/{test_run_id}");
+
+    let resp: CloudTestRunResponse = get_request(&test_run_url, cloud_token)
+        .send()
+        .await
+        .map_err(|e| {
+            eprintln!("{:?}", e.to_string());
+            e.to_string()
+        })?
+        .error_for_status()
+        .map_err(|e| {
+            eprintln!("{:?}", e.to_string());
+            e.to_string()
+        })?
+        .json()
+        .await
+        .map_err(|e| {
+            eprintln!("{:?}", e.to_string());
+            e.to_string()
+        })?;
+
+    Ok(resp.k6_run)
+}
+
+pub async fn stop_test_run(cloud_token: &str, test_run_id: u32) -> Result<(), String> {
+    let stop_url = format!("/loadtests/v2/runs/{test_run_id}/stop");
+
+    post_request(&stop_url, cloud_token)
+        .send()
+        .await
+        .map_err(|e| {
+            eprintln!("{:?}", e.to_string());
+            e.to_string()
+        })?
+        .error_for_status()
+        .map_err(|e| {
+            eprintln!("{:?}", e.to_string());
+            e.to_string()
+        })?;
+
+    Ok(())
+}
+
+// Checks that the cloud token is valid, that the cloud project exists,
+// and that the account the token belongs to has access to it.
+//
+// Problems with the configuration are reported through the returned
+// CloudConfigValidation, errors are only returned when the cloud API
+// couldn't be reached.
+pub async fn validate_cloud_config(
+    cloud_token: &str,
+    cloud_project_id: &str,
+) -> Result<CloudConfigValidation, String> {
+    let mut validation = CloudConfigValidation::default();
+
+    let account_resp = get_request("/v3/account/me", cloud_token)
+        .send()
+        .await
+        .map_err(|e| {
+            eprintln!("{:?}", e.to_string());
+            e.to_string()
+        })?;
+
+    match account_resp.status() {
+        status if status.is_success() => validation.token_valid = true,
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => {
+            validation.error = Some("The cloud token is invalid".to_string());
+            return Ok(validation);
+        }
+        status => return Err(format!("unexpected response from the cloud API: {}", status)),
+    }
+
+    if cloud_project_id.parse::<u32>().is_err() {
+        validation.error = Some("The cloud project id should be a number".to_string());
+        return Ok(validation);
+    }
+
+    let project_url = format!("/v3/projects/{cloud_project_id}");
+    let project_resp = get_request(&project_url, cloud_token)
+        .send()
+        .await
+        .map_err(|e| {
+            eprintln!("{:?}", e.to_string());
+            e.to_string()
+        })?;
+
+    match project_resp.status() {
+        status if status.is_success() => {
+            let project: CloudProject = project_resp.json().await.map_err(|e| {
+                eprintln!("{:?}", e.to_string());
+                e.to_string()
+            })?;
+
+            validation.project_exists = true;
+            validation.project_accessible = true;
+            validation.project_name = Some(project.name);
+        }
+        StatusCode::FORBIDDEN => {
+            validation.project_exists = true;
+            validation.error = Some(format!(
+                "The account has no access to the cloud project {}",
+                cloud_project_id
+            ));
+        }
+        StatusCode::NOT_FOUND => {
+            validation.error = Some(format!(
+                "The cloud project {} does not exist",
+                cloud_project_id
+            ));
+        }
+        status => return Err(format!("unexpected response from the cloud API: {}", status)),
+    }
+
+    Ok(validation)
+}
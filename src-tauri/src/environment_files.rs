@@ -0,0 +1,424 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::{fs, io};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::models::EnvironmentVariable;
+use crate::storage;
+
+// The formats environments can be exported to
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum EnvironmentFileFormat {
+    // A `.env` file of `KEY=value` lines
+    Dotenv,
+
+    // A JSON object mapping variable names to their values, as
+    // passed to k6 through `-e`
+    Json,
+}
+
+impl EnvironmentFileFormat {
+    // Returns the format matching the extension of the path, `.json`
+    // files being JSON and anything else `.env` files.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some(extension) if extension.eq_ignore_ascii_case("json") => Self::Json,
+            _ => Self::Dotenv,
+        }
+    }
+}
+
+// An environment read from a file
+#[derive(Debug)]
+pub struct ImportedEnvironment {
+    // The name of the environment, if the file holds one
+    pub name: Option<String>,
+
+    pub variables: BTreeMap<String, EnvironmentVariable>,
+}
+
+// A Postman environment export
+#[derive(Deserialize)]
+struct PostmanEnvironment {
+    #[serde(default)]
+    name: Option<String>,
+    values: Vec<PostmanValue>,
+}
+
+#[derive(Deserialize)]
+struct PostmanValue {
+    key: String,
+
+    #[serde(default)]
+    value: Value,
+
+    #[serde(default = "enabled")]
+    enabled: bool,
+
+    // `default` or `secret`
+    #[serde(default, rename = "type")]
+    kind: Option<String>,
+}
+
+fn enabled() -> bool {
+    true
+}
+
+// Reads the environment stored in the file at the given path.
+//
+// The format is detected from the content of the file: Postman environment
+// exports and JSON objects of variables are recognized, and anything else
+// is read as a `.env` file.
+pub fn read(path: &Path) -> io::Result<ImportedEnvironment> {
+    let content = fs::read_to_string(path)?;
+
+    match serde_json::from_str::<Value>(&content) {
+        Ok(json) => from_json(json),
+        Err(_) => Ok(ImportedEnvironment {
+            name: None,
+            variables: parse_dotenv(&content)?,
+        }),
+    }
+}
+
+// Writes the variables to the file at the given path, in the given format.
+//
+// Files holding secrets are private: only the current user can read them.
+pub fn write(
+    path: &Path,
+    variables: &BTreeMap<String, String>,
+    format: EnvironmentFileFormat,
+    private: bool,
+) -> io::Result<()> {
+    let content = match format {
+        EnvironmentFileFormat::Dotenv => to_dotenv(variables),
+        EnvironmentFileFormat::Json => serde_json::to_string_pretty(variables)? + "\n",
+    };
+
+    if private {
+        storage::write_atomic_private(path, content)
+    } else {
+        storage::write_atomic(path, content)
+    }
+}
+
+// Returns the name of the environment stored in the file at the given path,
+// derived from its name: `.env.staging`, `staging.env` and `staging.json`
+// all hold the `staging` environment.
+pub fn environment_name(path: &Path) -> Option<String> {
+    let file_name = path.file_name()?.to_str()?;
+
+    let name = if let Some(name) = file_name.strip_prefix(".env.") {
+        name
+    } else if let Some((name, _)) = file_name.rsplit_once('.') {
+        name
+    } else {
+        file_name
+    };
+
+    if name.is_empty() {
+        None
+    } else {
+        Some(name.to_string())
+    }
+}
+
+fn from_json(json: Value) -> io::Result<ImportedEnvironment> {
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+
+    if json.get("values").is_some_and(Value::is_array) {
+        let postman: PostmanEnvironment = serde_json::from_value(json)?;
+
+        let variables = postman
+            .values
+            .into_iter()
+            .filter(|value| value.enabled)
+            .map(|value| {
+                let secret = value.kind.as_deref() == Some("secret");
+                let variable = EnvironmentVariable::new(&json_to_string(&value.value), secret);
+                (value.key, variable)
+            })
+            .collect();
+
+        return Ok(ImportedEnvironment {
+            name: postman.name,
+            variables,
+        });
+    }
+
+    let object = json
+        .as_object()
+        .ok_or_else(|| invalid("expected a JSON object of variables"))?;
+
+    let mut variables = BTreeMap::new();
+    for (name, value) in object {
+        if value.is_object() || value.is_array() {
+            return Err(invalid(&format!("variable {} doesn't hold a value", name)));
+        }
+
+        variables.insert(
+            name.clone(),
+            EnvironmentVariable::new(&json_to_string(value), false),
+        );
+    }
+
+    Ok(ImportedEnvironment {
+        name: None,
+        variables,
+    })
+}
+
+// Returns the JSON value as the string k6 would see
+fn json_to_string(value: &Value) -> String {
+    match value {
+        Value::String(value) => value.clone(),
+        Value::Null => String::new(),
+        value => value.to_string(),
+    }
+}
+
+// Parses the content of a `.env` file.
+//
+// Lines are `KEY=value` pairs, optionally prefixed by `export`. Values can
+// be single quoted, taken literally, or double quoted, in which case the
+// `\n`, `\"` and `\\` escapes are supported. Comments start with `#`, at the
+// beginning of a line or after an unquoted value.
+fn parse_dotenv(content: &str) -> io::Result<BTreeMap<String, EnvironmentVariable>> {
+    let mut variables = BTreeMap::new();
+
+    for (index, line) in content.lines().enumerate() {
+        let invalid = |message: &str| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("line {}: {}", index + 1, message),
+            )
+        };
+
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let (name, value) = line
+            .split_once('=')
+            .ok_or_else(|| invalid("expected a KEY=value pair"))?;
+
+        let name = name.trim();
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            return Err(invalid("invalid variable name"));
+        }
+
+        let value = value.trim();
+        let value = if let Some(quoted) = value.strip_prefix('"') {
+            unescape_double_quoted(quoted).ok_or_else(|| invalid("unterminated quoted value"))?
+        } else if let Some(quoted) = value.strip_prefix('\'') {
+            let (value, _) = quoted
+                .split_once('\'')
+                .ok_or_else(|| invalid("unterminated quoted value"))?;
+            value.to_string()
+        } else {
+            match value.split_once(" #") {
+                Some((value, _)) => value.trim_end().to_string(),
+                None => value.to_string(),
+            }
+        };
+
+        variables.insert(name.to_string(), EnvironmentVariable::new(&value, false));
+    }
+
+    Ok(variables)
+}
+
+// Returns the value of a double quoted string, up to its closing quote
+fn unescape_double_quoted(quoted: &str) -> Option<String> {
+    let mut value = String::new();
+    let mut chars = quoted.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => return Some(value),
+            '\\' => match chars.next()? {
+                'n' => value.push('\n'),
+                'r' => value.push('\r'),
+                't' => value.push('\t'),
+                escaped => value.push(escaped),
+            },
+            c => value.push(c),
+        }
+    }
+
+    None
+}
+
+fn to_dotenv(variables: &BTreeMap<String, String>) -> String {
+    variables
+        .iter()
+        .map(|(name, value)| format!("{}={}\n", name, quote_dotenv(value)))
+        .collect()
+}
+
+// Quotes the value if it couldn't be read back as is
+fn quote_dotenv(value: &str) -> String {
+    let needs_quotes = value.is_empty()
+        || value.trim() != value
+        || value.contains(['"', '\'', '#', '\\', '\n', '\r', '\t']);
+    if !needs_quotes {
+        return value.to_string();
+    }
+
+    let escaped = value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+        .replace('\t', "\\t");
+    format!("\"{}\"", escaped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn values(variables: &BTreeMap<String, EnvironmentVariable>) -> BTreeMap<&str, &str> {
+        variables
+            .iter()
+            .map(|(name, variable)| (name.as_str(), variable.value.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn dotenv_files_are_parsed() {
+        let content = r#"
+# a comment
+export HOST=example.com
+PORT = 8080 # the port
+EMPTY=
+SINGLE='kept # as $is\n'
+DOUBLE="line\nbreak \"quoted\""
+URL=http://example.com/#anchor
+"#;
+
+        let variables = parse_dotenv(content).unwrap();
+        assert_eq!(
+            values(&variables),
+            BTreeMap::from([
+                ("HOST", "example.com"),
+                ("PORT", "8080"),
+                ("EMPTY", ""),
+                ("SINGLE", "kept # as $is\\n"),
+                ("DOUBLE", "line\nbreak \"quoted\""),
+                ("URL", "http://example.com/#anchor"),
+            ])
+        );
+        assert!(variables.values().all(|variable| !variable.secret));
+    }
+
+    #[test]
+    fn invalid_dotenv_lines_are_reported() {
+        let error = parse_dotenv("HOST=example.com\nnot a pair").unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(error.to_string().starts_with("line 2:"));
+
+        assert!(parse_dotenv("MY HOST=example.com").is_err());
+        assert!(parse_dotenv("=example.com").is_err());
+        assert!(parse_dotenv("HOST=\"example.com").is_err());
+        assert!(parse_dotenv("HOST='example.com").is_err());
+    }
+
+    #[test]
+    fn dotenv_files_are_read_back_as_written() {
+        let variables = BTreeMap::from(
+            [
+                ("PLAIN", "example.com"),
+                ("EMPTY", ""),
+                ("PADDED", "  padded "),
+                ("COMMENT", "value # not a comment"),
+                ("QUOTES", "it's \"quoted\""),
+                ("ESCAPES", "back\\slash\nnew line\ttab\r"),
+            ]
+            .map(|(name, value)| (name.to_string(), value.to_string())),
+        );
+
+        assert_eq!(quote_dotenv("example.com"), "example.com");
+        assert_eq!(quote_dotenv(""), "\"\"");
+
+        let parsed = parse_dotenv(&to_dotenv(&variables)).unwrap();
+        let parsed: BTreeMap<String, String> = parsed
+            .into_iter()
+            .map(|(name, variable)| (name, variable.value))
+            .collect();
+        assert_eq!(parsed, variables);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn files_holding_secrets_are_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let directory = tempfile::tempdir().unwrap();
+        let variables = BTreeMap::from([("TOKEN".to_string(), "s3cret".to_string())]);
+
+        let path = directory.path().join(".env");
+        write(&path, &variables, EnvironmentFileFormat::Dotenv, true).unwrap();
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        let imported = read(&path).unwrap();
+        assert_eq!(imported.variables["TOKEN"].value, "s3cret");
+    }
+
+    #[test]
+    fn postman_environments_keep_their_secrets_and_skip_disabled_values() {
+        let postman = json!({
+            "name": "staging",
+            "values": [
+                { "key": "HOST", "value": "example.com", "enabled": true },
+                { "key": "TOKEN", "value": "s3cret", "type": "secret" },
+                { "key": "RETRIES", "value": 3 },
+                { "key": "DISABLED", "value": "ignored", "enabled": false },
+            ],
+        });
+
+        let environment = from_json(postman).unwrap();
+        assert_eq!(environment.name.as_deref(), Some("staging"));
+        assert_eq!(
+            values(&environment.variables),
+            BTreeMap::from([
+                ("HOST", "example.com"),
+                ("TOKEN", "s3cret"),
+                ("RETRIES", "3")
+            ])
+        );
+        assert!(environment.variables["TOKEN"].secret);
+        assert!(!environment.variables["HOST"].secret);
+    }
+
+    #[test]
+    fn json_objects_hold_plain_values() {
+        let environment =
+            from_json(json!({ "HOST": "example.com", "PORT": 80, "NONE": null })).unwrap();
+        assert_eq!(environment.name, None);
+        assert_eq!(
+            values(&environment.variables),
+            BTreeMap::from([("HOST", "example.com"), ("PORT", "80"), ("NONE", "")])
+        );
+
+        assert!(from_json(json!({ "NESTED": { "HOST": "example.com" } })).is_err());
+        assert!(from_json(json!(["HOST"])).is_err());
+    }
+
+    #[test]
+    fn environments_are_named_after_their_file() {
+        let name = |path: &str| environment_name(Path::new(path));
+
+        assert_eq!(name("/tmp/.env.staging").as_deref(), Some("staging"));
+        assert_eq!(name("/tmp/staging.env").as_deref(), Some("staging"));
+        assert_eq!(name("/tmp/staging.json").as_deref(), Some("staging"));
+        assert_eq!(name("/tmp/staging").as_deref(), Some("staging"));
+        assert_eq!(name("/tmp/.env"), None);
+    }
+}
@@ -0,0 +1,613 @@
+use std::path::Path;
+use std::{fs, io};
+
+use chrono::{DateTime, SecondsFormat};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+
+use crate::models::{RecordedFlow, RecordedRequest, RecordedResponse};
+use crate::storage;
+
+// The version of the HAR specification we produce
+const HAR_VERSION: &str = "1.2";
+
+// Har is the root of an HTTP Archive, as defined by the HAR 1.2 specification.
+//
+// See http://www.softwareishard.com/blog/har-12-spec/
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Har {
+    pub log: Log,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Log {
+    pub version: String,
+    pub creator: Creator,
+    pub entries: Vec<Entry>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Creator {
+    pub name: String,
+    pub version: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Entry {
+    // ISO 8601 date of the start of the request
+    pub started_date_time: String,
+
+    // Total elapsed time of the request, in milliseconds
+    pub time: f64,
+
+    pub request: Request,
+    pub response: Response,
+
+    #[serde(default)]
+    pub cache: Cache,
+
+    #[serde(default)]
+    pub timings: Timings,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Request {
+    pub method: String,
+    pub url: String,
+    pub http_version: String,
+
+    #[serde(default)]
+    pub cookies: Vec<Cookie>,
+
+    #[serde(default)]
+    pub headers: Vec<Header>,
+
+    #[serde(default)]
+    pub query_string: Vec<QueryParam>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub post_data: Option<PostData>,
+
+    #[serde(default = "unknown_size")]
+    pub headers_size: i64,
+
+    #[serde(default = "unknown_size")]
+    pub body_size: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Response {
+    pub status: u16,
+
+    #[serde(default)]
+    pub status_text: String,
+
+    #[serde(default)]
+    pub http_version: String,
+
+    #[serde(default)]
+    pub cookies: Vec<Cookie>,
+
+    #[serde(default)]
+    pub headers: Vec<Header>,
+
+    pub content: Content,
+
+    #[serde(default, rename = "redirectURL")]
+    pub redirect_url: String,
+
+    #[serde(default = "unknown_size")]
+    pub headers_size: i64,
+
+    #[serde(default = "unknown_size")]
+    pub body_size: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Cookie {
+    pub name: String,
+    pub value: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Header {
+    pub name: String,
+    pub value: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct QueryParam {
+    pub name: String,
+    pub value: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PostData {
+    #[serde(default)]
+    pub mime_type: String,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Content {
+    pub size: i64,
+
+    #[serde(default)]
+    pub mime_type: String,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Cache {}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Timings {
+    pub send: f64,
+    pub wait: f64,
+    pub receive: f64,
+}
+
+impl Default for Timings {
+    fn default() -> Self {
+        Self {
+            send: 0.0,
+            wait: -1.0,
+            receive: 0.0,
+        }
+    }
+}
+
+// HAR uses -1 for sizes that are not known
+fn unknown_size() -> i64 {
+    -1
+}
+
+fn known_size(size: i64) -> Option<u64> {
+    u64::try_from(size).ok()
+}
+
+// Returns the size of a body as it was transferred: the one reported by the
+// recorder, or else the size of its content unless it wasn't captured.
+fn body_size(content: &str, content_length: Option<u64>, redacted: bool) -> i64 {
+    match content_length {
+        Some(content_length) => content_length as i64,
+        None if redacted => unknown_size(),
+        None => content.len() as i64,
+    }
+}
+
+impl Har {
+    // Builds an HTTP Archive out of recorded flows.
+    //
+    // Flows which never received a response are left out, as HAR
+    // entries require one.
+    pub fn from_flows(flows: &[RecordedFlow]) -> Self {
+        let entries = flows
+            .iter()
+            .filter_map(|flow| {
+                let response = flow.response.as_ref()?;
+                Some(Entry::from_flow(flow, response))
+            })
+            .collect();
+
+        Self {
+            log: Log {
+                version: HAR_VERSION.to_string(),
+                creator: Creator {
+                    name: "kroco6".to_string(),
+                    version: env!("CARGO_PKG_VERSION").to_string(),
+                },
+                entries,
+            },
+        }
+    }
+
+    // Reads an HTTP Archive from the file at the given path.
+    pub fn read(path: &Path) -> io::Result<Self> {
+        let file = fs::File::open(path)?;
+        let har = serde_json::from_reader(file)?;
+        Ok(har)
+    }
+
+    // Converts the entries of the HTTP Archive into recorded flows, as
+    // if they had been captured by the recorder.
+    //
+    // Entries whose URL can't be parsed are skipped.
+    pub fn to_flows(&self) -> Vec<RecordedFlow> {
+        self.log
+            .entries
+            .iter()
+            .enumerate()
+            .filter_map(|(index, entry)| entry.to_flow(&format!("har-{}", index)))
+            .collect()
+    }
+
+    // Writes the HTTP Archive to the file at the given path.
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        storage::write_json_atomic(path, self)
+    }
+}
+
+impl Entry {
+    fn from_flow(flow: &RecordedFlow, response: &RecordedResponse) -> Self {
+        let request_start = flow.request.timestamp_start;
+        let request_end = flow.request.timestamp_end.unwrap_or(request_start);
+        let response_start = response.timestamp_start.unwrap_or(request_end);
+        let response_end = response.timestamp_end.unwrap_or(response_start);
+
+        let timings = Timings {
+            send: to_millis(request_end - request_start),
+            wait: to_millis(response_start - request_end),
+            receive: to_millis(response_end - response_start),
+        };
+
+        Self {
+            started_date_time: to_iso8601(request_start),
+            time: timings.send + timings.wait + timings.receive,
+            request: Request::from_recorded(&flow.request),
+            response: Response::from_recorded(response),
+            cache: Cache::default(),
+            timings,
+        }
+    }
+
+    fn to_flow(&self, id: &str) -> Option<RecordedFlow> {
+        let url = Url::parse(&self.request.url).ok()?;
+
+        let path = match url.query() {
+            Some(query) => format!("{}?{}", url.path(), query),
+            None => url.path().to_string(),
+        };
+
+        let request_start = from_iso8601(&self.started_date_time);
+        let request_end = request_start + to_seconds(self.timings.send);
+        let response_start = request_end + to_seconds(self.timings.wait);
+        let response_end = response_start + to_seconds(self.timings.receive);
+
+        let request = RecordedRequest {
+            method: self.request.method.clone(),
+            scheme: url.scheme().to_string(),
+            host: url.host_str()?.to_string(),
+            port: url.port_or_known_default()?,
+            path,
+            http_version: self.request.http_version.clone(),
+            headers: from_headers(&self.request.headers),
+            content: self
+                .request
+                .post_data
+                .as_ref()
+                .and_then(|post_data| post_data.text.clone())
+                .unwrap_or_default(),
+            content_length: known_size(self.request.body_size),
+            timestamp_start: request_start,
+            timestamp_end: Some(request_end),
+        };
+
+        // binary content is base64 encoded in HAR files, we only keep text
+        let content = match self.response.content.encoding {
+            None => self.response.content.text.clone().unwrap_or_default(),
+            Some(_) => String::new(),
+        };
+
+        let response = RecordedResponse {
+            http_version: self.response.http_version.clone(),
+            status_code: self.response.status,
+            reason: self.response.status_text.clone(),
+            headers: from_headers(&self.response.headers),
+            content,
+            content_length: known_size(self.response.body_size),
+            timestamp_start: Some(response_start),
+            timestamp_end: Some(response_end),
+        };
+
+        Some(RecordedFlow {
+            id: id.to_string(),
+            timestamp_created: request_start,
+            request,
+            response: Some(response),
+        })
+    }
+}
+
+impl Request {
+    fn from_recorded(request: &RecordedRequest) -> Self {
+        let url = request.url();
+
+        let query_string = Url::parse(&url)
+            .map(|url| {
+                url.query_pairs()
+                    .map(|(name, value)| QueryParam {
+                        name: name.to_string(),
+                        value: value.to_string(),
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        // bodies the recorder didn't capture are left out, only their type is known
+        let post_data = if request.content.is_empty() {
+            None
+        } else {
+            Some(PostData {
+                mime_type: header_value(&request.headers, "content-type")
+                    .unwrap_or_default()
+                    .to_string(),
+                text: Some(request.content.clone()).filter(|_| !request.is_content_redacted()),
+            })
+        };
+
+        Self {
+            method: request.method.clone(),
+            url,
+            http_version: request.http_version.clone(),
+            cookies: request_cookies(&request.headers),
+            headers: to_headers(&request.headers),
+            query_string,
+            post_data,
+            headers_size: unknown_size(),
+            body_size: body_size(
+                &request.content,
+                request.content_length,
+                request.is_content_redacted(),
+            ),
+        }
+    }
+}
+
+impl Response {
+    fn from_recorded(response: &RecordedResponse) -> Self {
+        let mime_type = header_value(&response.headers, "content-type")
+            .unwrap_or_default()
+            .to_string();
+
+        let body_size = body_size(
+            &response.content,
+            response.content_length,
+            response.is_content_redacted(),
+        );
+        let (size, text) = if response.is_content_redacted() {
+            (body_size, None)
+        } else {
+            (
+                response.content.len() as i64,
+                Some(response.content.clone()),
+            )
+        };
+
+        Self {
+            status: response.status_code,
+            status_text: response.reason.clone(),
+            http_version: response.http_version.clone(),
+            cookies: response_cookies(&response.headers),
+            headers: to_headers(&response.headers),
+            content: Content {
+                size,
+                mime_type,
+                text,
+                encoding: None,
+            },
+            redirect_url: header_value(&response.headers, "location")
+                .unwrap_or_default()
+                .to_string(),
+            headers_size: unknown_size(),
+            body_size,
+        }
+    }
+}
+
+// Returns the value of the first header with the given name, if any
+fn header_value<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(header_name, _)| header_name.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.as_str())
+}
+
+fn to_headers(headers: &[(String, String)]) -> Vec<Header> {
+    headers
+        .iter()
+        .map(|(name, value)| Header {
+            name: name.clone(),
+            value: value.clone(),
+        })
+        .collect()
+}
+
+fn from_headers(headers: &[Header]) -> Vec<(String, String)> {
+    headers
+        .iter()
+        .map(|header| (header.name.clone(), header.value.clone()))
+        .collect()
+}
+
+// Parses the cookies sent through `Cookie` request headers
+fn request_cookies(headers: &[(String, String)]) -> Vec<Cookie> {
+    headers
+        .iter()
+        .filter(|(name, _)| name.eq_ignore_ascii_case("cookie"))
+        .flat_map(|(_, value)| value.split(';'))
+        .filter_map(parse_cookie)
+        .collect()
+}
+
+// Parses the cookies set through `Set-Cookie` response headers,
+// ignoring their attributes.
+fn response_cookies(headers: &[(String, String)]) -> Vec<Cookie> {
+    headers
+        .iter()
+        .filter(|(name, _)| name.eq_ignore_ascii_case("set-cookie"))
+        .filter_map(|(_, value)| value.split(';').next())
+        .filter_map(parse_cookie)
+        .collect()
+}
+
+fn parse_cookie(pair: &str) -> Option<Cookie> {
+    let (name, value) = pair.trim().split_once('=')?;
+    Some(Cookie {
+        name: name.to_string(),
+        value: value.to_string(),
+    })
+}
+
+// Converts a duration in seconds to milliseconds
+fn to_millis(seconds: f64) -> f64 {
+    (seconds * 1000.0).max(0.0)
+}
+
+// Converts a duration in milliseconds to seconds, HAR uses
+// -1 for timings that don't apply.
+fn to_seconds(millis: f64) -> f64 {
+    millis.max(0.0) / 1000.0
+}
+
+// Formats a unix timestamp in seconds as an ISO 8601 date
+fn to_iso8601(timestamp: f64) -> String {
+    DateTime::from_timestamp_millis((timestamp * 1000.0) as i64)
+        .unwrap_or_default()
+        .to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+// Parses an ISO 8601 date as a unix timestamp in seconds
+fn from_iso8601(date: &str) -> f64 {
+    DateTime::parse_from_rfc3339(date)
+        .map(|date| date.timestamp_millis() as f64 / 1000.0)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::REDACTED_CONTENT;
+
+    fn flow(id: &str, response: Option<RecordedResponse>) -> RecordedFlow {
+        RecordedFlow {
+            id: id.to_string(),
+            timestamp_created: 1700000000.0,
+            request: RecordedRequest {
+                method: "POST".to_string(),
+                scheme: "https".to_string(),
+                host: "example.com".to_string(),
+                port: 8443,
+                path: "/api/items?page=2".to_string(),
+                http_version: "HTTP/1.1".to_string(),
+                headers: vec![
+                    ("Content-Type".to_string(), "application/json".to_string()),
+                    ("Cookie".to_string(), "session=abc; theme=dark".to_string()),
+                ],
+                content: r#"{"name":"item"}"#.to_string(),
+                content_length: Some(15),
+                timestamp_start: 1700000000.0,
+                timestamp_end: Some(1700000000.25),
+            },
+            response,
+        }
+    }
+
+    fn response() -> RecordedResponse {
+        RecordedResponse {
+            http_version: "HTTP/1.1".to_string(),
+            status_code: 201,
+            reason: "Created".to_string(),
+            headers: vec![
+                ("Content-Type".to_string(), "application/json".to_string()),
+                (
+                    "Set-Cookie".to_string(),
+                    "id=42; Path=/; HttpOnly".to_string(),
+                ),
+            ],
+            content: r#"{"id":42}"#.to_string(),
+            content_length: Some(9),
+            timestamp_start: Some(1700000000.5),
+            timestamp_end: Some(1700000001.0),
+        }
+    }
+
+    #[test]
+    fn flows_survive_a_round_trip() {
+        let original = flow("1", Some(response()));
+
+        let har = Har::from_flows(std::slice::from_ref(&original));
+        let json = serde_json::to_string(&har).unwrap();
+        let flows = serde_json::from_str::<Har>(&json).unwrap().to_flows();
+
+        assert_eq!(flows.len(), 1);
+        let (request, response) = (&flows[0].request, flows[0].response.as_ref().unwrap());
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.url(), original.request.url());
+        assert_eq!(request.headers, original.request.headers);
+        assert_eq!(request.content, original.request.content);
+        assert_eq!(request.timestamp_start, 1700000000.0);
+        assert_eq!(request.timestamp_end, Some(1700000000.25));
+
+        let original_response = original.response.unwrap();
+        assert_eq!(response.status_code, 201);
+        assert_eq!(response.reason, "Created");
+        assert_eq!(response.headers, original_response.headers);
+        assert_eq!(response.content, original_response.content);
+        assert_eq!(response.timestamp_start, Some(1700000000.5));
+        assert_eq!(response.timestamp_end, Some(1700000001.0));
+    }
+
+    #[test]
+    fn bodies_not_captured_are_left_out() {
+        let mut original = flow("1", Some(response()));
+        original.request.content = REDACTED_CONTENT.to_string();
+        original.request.content_length = Some(2048);
+        let response = original.response.as_mut().unwrap();
+        response.content = REDACTED_CONTENT.to_string();
+        response.content_length = None;
+
+        let har = Har::from_flows(&[original]);
+        let entry = &har.log.entries[0];
+
+        let post_data = entry.request.post_data.as_ref().unwrap();
+        assert_eq!(post_data.mime_type, "application/json");
+        assert_eq!(post_data.text, None);
+        assert_eq!(entry.request.body_size, 2048);
+        assert_eq!(entry.response.content.text, None);
+        assert_eq!(entry.response.content.size, -1);
+        assert_eq!(entry.response.body_size, -1);
+    }
+
+    #[test]
+    fn cookies_are_parsed_from_headers() {
+        let har = Har::from_flows(&[flow("1", Some(response()))]);
+        let entry = &har.log.entries[0];
+
+        let names = |cookies: &[Cookie]| -> Vec<String> {
+            cookies.iter().map(|cookie| cookie.name.clone()).collect()
+        };
+        assert_eq!(names(&entry.request.cookies), vec!["session", "theme"]);
+        assert_eq!(names(&entry.response.cookies), vec!["id"]);
+        assert_eq!(entry.response.cookies[0].value, "42");
+    }
+
+    #[test]
+    fn flows_without_a_response_are_left_out() {
+        let har = Har::from_flows(&[flow("1", None), flow("2", Some(response()))]);
+
+        assert_eq!(har.log.entries.len(), 1);
+    }
+
+    #[test]
+    fn entries_with_invalid_urls_are_skipped() {
+        let mut har = Har::from_flows(&[flow("1", Some(response())), flow("2", Some(response()))]);
+        har.log.entries[0].request.url = "not a url".to_string();
+
+        let flows = har.to_flows();
+        assert_eq!(flows.len(), 1);
+        assert_eq!(flows[0].id, "har-1");
+    }
+}
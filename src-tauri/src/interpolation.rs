@@ -0,0 +1,333 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::{env, io};
+
+// The prefix of references to variables of the app's own environment
+const SHELL_VARIABLE_PREFIX: &str = "env:";
+
+// Resolves the references the values of the variables hold.
+//
+// `${NAME}` is replaced by the value of the variable `NAME`, which can hold
+// references of its own, and `${env:NAME}` by the value of the environment
+// variable `NAME` of the app's process. `$${` escapes a literal `${`.
+//
+// The values of the `literals` variables, such as secrets, are taken as is:
+// the references they seem to hold are not resolved.
+//
+// References to undefined variables, and variables referencing each other
+// in a cycle, are errors naming the variable that can't be resolved.
+pub fn interpolate(
+    variables: &BTreeMap<String, String>,
+    literals: &BTreeSet<String>,
+) -> io::Result<BTreeMap<String, String>> {
+    let mut interpolator = Interpolator {
+        variables,
+        literals,
+        resolved: BTreeMap::new(),
+        stack: vec![],
+    };
+
+    for name in variables.keys() {
+        interpolator.resolve(name)?;
+    }
+
+    Ok(interpolator.resolved)
+}
+
+// Returns the names of the variables whose resolved value is sensitive: the
+// given secrets, along with the variables referencing them, or the app's
+// environment, directly or through other variables.
+//
+// Malformed values and references to undefined variables are left for
+// interpolate to report.
+pub fn sensitive_variables(
+    variables: &BTreeMap<String, String>,
+    secrets: &BTreeSet<String>,
+) -> BTreeSet<String> {
+    let mut sensitive = secrets.clone();
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+
+        for (name, raw) in variables {
+            if sensitive.contains(name) {
+                continue;
+            }
+
+            let draws_on_sensitive = segments(raw).is_some_and(|segments| {
+                segments.iter().any(|segment| match segment {
+                    Segment::Text(_) => false,
+                    Segment::Variable(reference) => sensitive.contains(*reference),
+                    Segment::Shell(_) => true,
+                })
+            });
+
+            if draws_on_sensitive {
+                sensitive.insert(name.clone());
+                changed = true;
+            }
+        }
+    }
+
+    sensitive
+}
+
+// A part of the value of a variable
+enum Segment<'a> {
+    // Literal text
+    Text(&'a str),
+
+    // A reference to another variable
+    Variable(&'a str),
+
+    // A reference to a variable of the app's environment
+    Shell(&'a str),
+}
+
+// Splits a value into its literal text and the references it holds,
+// returning None if a reference is unterminated.
+fn segments(raw: &str) -> Option<Vec<Segment<'_>>> {
+    let mut segments = vec![];
+    let mut rest = raw;
+
+    while let Some(start) = rest.find("${") {
+        // an escaped reference is kept as is, minus its escape
+        if rest[..start].ends_with('$') {
+            segments.push(Segment::Text(&rest[..start - 1]));
+            segments.push(Segment::Text("${"));
+            rest = &rest[start + 2..];
+            continue;
+        }
+
+        segments.push(Segment::Text(&rest[..start]));
+        rest = &rest[start + 2..];
+
+        let end = rest.find('}')?;
+        let reference = rest[..end].trim();
+        rest = &rest[end + 1..];
+
+        match reference.strip_prefix(SHELL_VARIABLE_PREFIX) {
+            Some(shell_variable) => segments.push(Segment::Shell(shell_variable.trim())),
+            None => segments.push(Segment::Variable(reference)),
+        }
+    }
+
+    segments.push(Segment::Text(rest));
+    Some(segments)
+}
+
+struct Interpolator<'a> {
+    variables: &'a BTreeMap<String, String>,
+
+    // The variables whose value isn't interpolated
+    literals: &'a BTreeSet<String>,
+
+    // The variables whose value has been resolved
+    resolved: BTreeMap<String, String>,
+
+    // The variables being resolved, each one referenced by the previous one
+    stack: Vec<&'a str>,
+}
+
+impl<'a> Interpolator<'a> {
+    fn resolve(&mut self, name: &'a str) -> io::Result<String> {
+        if let Some(value) = self.resolved.get(name) {
+            return Ok(value.clone());
+        }
+
+        if let Some(position) = self.stack.iter().position(|variable| *variable == name) {
+            let mut cycle = self.stack[position..].to_vec();
+            cycle.push(name);
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "variable {} references itself: {}",
+                    name,
+                    cycle.join(" -> ")
+                ),
+            ));
+        }
+
+        let (name, raw) = self
+            .variables
+            .get_key_value(name)
+            .expect("resolved variables are defined");
+
+        if self.literals.contains(name) {
+            self.resolved.insert(name.clone(), raw.clone());
+            return Ok(raw.clone());
+        }
+
+        self.stack.push(name);
+        let value = self.interpolate_value(name, raw)?;
+        self.stack.pop();
+
+        self.resolved.insert(name.clone(), value.clone());
+        Ok(value)
+    }
+
+    fn interpolate_value(&mut self, name: &str, raw: &'a str) -> io::Result<String> {
+        let segments = segments(raw).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("variable {} holds an unterminated reference", name),
+            )
+        })?;
+
+        let mut value = String::new();
+        for segment in segments {
+            match segment {
+                Segment::Text(text) => value.push_str(text),
+                Segment::Shell(shell_variable) => {
+                    let shell_value = env::var(shell_variable).map_err(|_| {
+                        io::Error::new(
+                            io::ErrorKind::NotFound,
+                            format!(
+                                "variable {} references the undefined environment variable {}",
+                                name, shell_variable
+                            ),
+                        )
+                    })?;
+                    value.push_str(&shell_value);
+                }
+                Segment::Variable(reference) => {
+                    let (reference, _) =
+                        self.variables.get_key_value(reference).ok_or_else(|| {
+                            io::Error::new(
+                                io::ErrorKind::NotFound,
+                                format!(
+                                    "variable {} references the undefined variable {}",
+                                    name, reference
+                                ),
+                            )
+                        })?;
+                    value.push_str(&self.resolve(reference)?);
+                }
+            }
+        }
+
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn variables(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn references_are_resolved_recursively() {
+        let resolved = interpolate(
+            &variables(&[
+                ("URL", "${BASE}/api"),
+                ("BASE", "https://${ HOST }"),
+                ("HOST", "example.com"),
+            ]),
+            &BTreeSet::new(),
+        )
+        .unwrap();
+
+        assert_eq!(resolved["URL"], "https://example.com/api");
+        assert_eq!(resolved["BASE"], "https://example.com");
+        assert_eq!(resolved["HOST"], "example.com");
+    }
+
+    #[test]
+    fn escaped_references_are_kept_literally() {
+        let resolved = interpolate(
+            &variables(&[("TEMPLATE", "$${HOST} is ${HOST}"), ("HOST", "example.com")]),
+            &BTreeSet::new(),
+        )
+        .unwrap();
+
+        assert_eq!(resolved["TEMPLATE"], "${HOST} is example.com");
+    }
+
+    #[test]
+    fn app_environment_variables_are_referenced_with_their_prefix() {
+        let path = env::var("PATH").unwrap();
+        let resolved =
+            interpolate(&variables(&[("SEARCH", "${env:PATH}")]), &BTreeSet::new()).unwrap();
+        assert_eq!(resolved["SEARCH"], path);
+
+        let error = interpolate(
+            &variables(&[("MISSING", "${env:KROCO6_UNDEFINED}")]),
+            &BTreeSet::new(),
+        )
+        .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn undefined_references_are_errors() {
+        let error =
+            interpolate(&variables(&[("URL", "https://${HOST}")]), &BTreeSet::new()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::NotFound);
+        assert!(error.to_string().contains("undefined variable HOST"));
+
+        let error =
+            interpolate(&variables(&[("URL", "https://${HOST")]), &BTreeSet::new()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn cycles_are_errors() {
+        let error = interpolate(
+            &variables(&[("A", "${B}"), ("B", "${C}"), ("C", "${A}")]),
+            &BTreeSet::new(),
+        )
+        .unwrap_err();
+
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(
+            error.to_string(),
+            "variable A references itself: A -> B -> C -> A"
+        );
+
+        assert!(interpolate(&variables(&[("SELF", "${SELF}")]), &BTreeSet::new()).is_err());
+    }
+
+    #[test]
+    fn literal_values_are_not_interpolated() {
+        let resolved = interpolate(
+            &variables(&[
+                ("TOKEN", "s3${cret"),
+                ("PASSWORD", "${HOST}"),
+                ("AUTH", "Bearer ${TOKEN}"),
+                ("HOST", "example.com"),
+            ]),
+            &BTreeSet::from(["TOKEN".to_string(), "PASSWORD".to_string()]),
+        )
+        .unwrap();
+
+        assert_eq!(resolved["TOKEN"], "s3${cret");
+        assert_eq!(resolved["PASSWORD"], "${HOST}");
+        assert_eq!(resolved["AUTH"], "Bearer s3${cret");
+    }
+
+    #[test]
+    fn variables_drawing_on_secrets_are_sensitive() {
+        let sensitive = sensitive_variables(
+            &variables(&[
+                ("TOKEN", ""),
+                ("HEADER", "Bearer ${TOKEN}"),
+                ("AUTH", "${HEADER}"),
+                ("HOME", "${env:HOME}"),
+                ("ESCAPED", "$${TOKEN}"),
+                ("HOST", "example.com"),
+            ]),
+            &BTreeSet::from(["TOKEN".to_string()]),
+        );
+
+        assert_eq!(
+            sensitive,
+            BTreeSet::from(["AUTH", "HEADER", "HOME", "TOKEN"].map(String::from))
+        );
+    }
+}
@@ -1,29 +1,64 @@
 // Prevents additional console window on Windows in release, DO NOT REMOVE!!
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+mod browser;
 mod models;
 mod operations;
 mod cloud;
 mod executable;
-
+mod certificates;
+mod environment_files;
+mod har;
+mod interpolation;
+mod recorder;
+mod recording;
+mod runner;
+mod secrets;
+mod storage;
+
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
-use std::io::{Read, Write, BufRead, BufReader};
-use std::path::Path;
-use std::process::{Command, Stdio};
-use std::sync::Mutex;
+use std::io::{self, Write, BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use serde_json::Value;
 use tauri::{Manager, Window};
 use tauri::api::process;
 use regex::Regex;
 use tokio::task;
-use tokio::sync::mpsc;
-use headless_chrome::browser::default_executable;
-use sysinfo::System;
 
 use crate::operations::ProjectManager;
+use crate::secrets::SecretStore;
+
+// How often the cloud API is polled for the status of a running cloud test.
+const CLOUD_RUN_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+// How many times in a row the cloud API may fail to report the status of a
+// cloud test before we stop polling it.
+const CLOUD_RUN_MAX_FAILED_POLLS: u32 = 12;
+
+// How often the current recorder session is saved to its project while recording.
+const RECORDING_AUTOSAVE_INTERVAL: Duration = Duration::from_secs(5);
+
+// How long the recorder proxy is given to start accepting connections.
+const RECORDER_STARTUP_TIMEOUT: Duration = Duration::from_secs(15);
+
+// How long the proxy of a stopped recorder session is given to release its port.
+const RECORDER_PORT_RELEASE_TIMEOUT: Duration = Duration::from_secs(3);
+
+// How long a k6 process attached to a stopped cloud test is given to exit on its own.
+const CLOUD_RUN_STOP_GRACE_PERIOD: Duration = Duration::from_secs(10);
 
 fn main() {
-    let application_state = ApplicationState::default();
+    let context = tauri::generate_context!();
+
+    // another instance of the app holding the storage would overwrite our changes
+    let application_state = match ApplicationState::new() {
+        Ok(application_state) => application_state,
+        Err(e) => exit_with_error(context, &e),
+    };
 
     // Initialize the application state's ProjectManager instance
     // to ensure that the underlying projects directory exists
@@ -49,6 +84,7 @@ fn main() {
             open_run_window,
             run_script,
             run_script_in_cloud,
+            stop_cloud_test_run,
             list_projects,
             get_project,
             create_project,
@@ -56,34 +92,86 @@ fn main() {
             get_cloud_token,
             load_environments,
             save_environments,
+            create_environment,
+            rename_environment,
+            delete_environment,
+            set_variable,
+            unset_variable,
+            set_active_environment,
+            load_project_environments,
+            save_project_environments,
+            load_effective_environments,
+            get_effective_variables,
+            get_interpolated_environment,
+            import_environment,
+            export_environment,
             create_test,
             list_tests,
             get_test,
             save_test,
+            get_test_metadata,
+            update_test_metadata,
             load_project_config,
             save_project_config,
+            validate_cloud_config,
             open_browser,
+            list_browsers,
+            pause_recording,
+            resume_recording,
+            add_recording_marker,
+            export_recording_har,
+            import_har,
+            generate_script_from_recording,
+            list_recordings,
+            load_recording,
+            delete_recording,
+            list_suites,
+            get_suite,
+            save_suite,
+            delete_suite,
+            run_suite,
             is_k6_executable_installed,
             download_k6_executable,
         ])
-        .run(tauri::generate_context!())
+        .build(context)
+        .expect("error while building tauri application")
+        .run(|handle, event| {
+            // make sure the recorder's proxy and browser don't outlive the application
+            if let tauri::RunEvent::Exit = event {
+                handle.state::<ApplicationState>().stop_recorder();
+            }
+        });
+}
+
+// Reports an error preventing the application from starting, then exits.
+//
+// Dialogs need the event loop to be running, so the application is started
+// without any state nor commands, for the sole purpose of showing the error.
+fn exit_with_error(context: tauri::Context<tauri::utils::assets::EmbeddedAssets>, message: &str) -> ! {
+    eprintln!("{}", message);
+
+    let message = message.to_string();
+    tauri::Builder::default()
+        .setup(move |_| {
+            tauri::api::dialog::MessageDialogBuilder::new("Kroco6", message)
+                .kind(tauri::api::dialog::MessageDialogKind::Error)
+                .show(|_| std::process::exit(1));
+            Ok(())
+        })
+        .run(context)
         .expect("error while running tauri application");
+
+    std::process::exit(1)
 }
 
 #[tauri::command]
 async fn get_cloud_tests(state: tauri::State<'_, ApplicationState>, project_name: &str) -> Result<Vec<models::CloudTest>, String> {
-    let project_config = state
-        .project_manager
-        .load_project_config(project_name)
-        .map_err(|e| e.to_string())?;
+    let cloud_credentials = state.cloud_credentials(Some(project_name))?;
+    let cloud_project_id = cloud_credentials
+        .project_id
+        .ok_or("missing cloud_project_id config".to_string())?;
 
-    if project_config.cloud_token.is_none() || project_config.cloud_project_id.is_none() {
-        return Err("missing cloud_token/cloud_project_id config".to_string());
-    }
-    let cloud_token = project_config.cloud_token.unwrap();
-    let cloud_project_id = project_config.cloud_project_id.unwrap();
-
-    let cloud_tests: Vec<models::CloudTest> = cloud::get_cloud_tests(&cloud_token, &cloud_project_id)
+    let cloud_tests: Vec<models::CloudTest> = cloud::get_cloud_tests(&cloud_credentials.token, &cloud_project_id)
         .await
         .map_err(|e| {
             e.to_string()
@@ -97,48 +185,105 @@ struct Payload {
 }
 
 #[tauri::command]
-async fn open_browser(handle: tauri::AppHandle, window: Window) {
+async fn open_browser(
+    handle: tauri::AppHandle,
+    state: tauri::State<'_, ApplicationState>,
+    window: Window,
+    project_name: Option<String>,
+    filter: Option<models::RecordingFilter>,
+    proxy_port: Option<u16>,
+    browser_path: Option<String>,
+) -> Result<(), String> {
+    let browser_path = browser::browser_path(browser_path.as_deref()).map_err(|e| recorder_failed(&window, e.to_string()))?;
+
+    // only one recorder session runs at a time, and the previous one may
+    // be using the port the new one is configured with
+    if let Some(stop_listener) = state.recorder_stop_listener.lock().unwrap().take() {
+        window.unlisten(stop_listener);
+    }
+    state.stop_recorder();
+
+    // use the configured port if any, a free one otherwise
+    let proxy_port = match proxy_port {
+        Some(proxy_port) => {
+            if !recorder::wait_until_port_free(proxy_port, RECORDER_PORT_RELEASE_TIMEOUT).await {
+                return Err(recorder_failed(&window, format!("port {} is already in use", proxy_port)));
+            }
+            proxy_port
+        }
+        None => browser::free_port().map_err(|e| recorder_failed(&window, e.to_string()))?,
+    };
+    let proxy_port_arg = proxy_port.to_string();
+    let proxy_server = format!("--proxy-server=http://localhost:{}", proxy_port);
+
     let resource_path = handle.path_resolver()
       .resolve_resource("resources/json_output.py")
       .expect("failed to resolve resource");
     let resource_path = format!("{}", resource_path.display());
 
-    let certificates_path = handle.path_resolver()
-      .resolve_resource("resources/certificates")
-      .expect("failed to resolve resource");
-    let certificates_setting = format!("confdir={}", certificates_path.display());
-
+    let certificate_authority = certificates::CertificateAuthority::load_or_generate(&state.certificates_path)
+      .map_err(|e| recorder_failed(&window, format!("failed to set up the recorder's certificate authority: {}", e)))?;
+    let certificates_setting = format!("confdir={}", certificate_authority.directory.display());
 
     let (mut rx, child) = process::Command::new_sidecar("mitmdump")
-      .expect("failed to create `mitmdump` binary command")
-      .args(["-q", "-s", &resource_path, "--set", &certificates_setting])
+      .map_err(|e| recorder_failed(&window, format!("failed to create the `mitmdump` command: {}", e)))?
+      .args(["-q", "-s", &resource_path, "--set", &certificates_setting, "--listen-port", &proxy_port_arg])
       .spawn()
-      .expect("Failed to spawn sidecar");
-
-    // the first event lets us know that the proxy started, it could either be the actual started
-    // message or a warning from the tool, so we need to handle that case
-    match rx.recv().await.unwrap() {
-        process::CommandEvent::Stdout(line) => {
-            // we got the proxy start event so we can continue
-            println!("{:?}", line);
-        }
-        process::CommandEvent::Stderr(line) => {
-            // we got the warning from the tool first so we still have to wait for the start event
-            println!("{:?}", line);
-            let event = rx.recv().await;
-            println!("{:?}", event);
-        }
-        _ => {}
-    };
+      .map_err(|e| recorder_failed(&window, format!("failed to start the recorder proxy: {}", e)))?;
+
+    // from now on the proxy is owned by the session, and gets cleaned up along with it
+    let mut recorder_session = recorder::RecorderSession::new(child);
+
+    // wait for the proxy to actually accept connections before pointing the browser to it
+    if let Err(reason) = recorder::wait_until_ready(&mut rx, proxy_port, RECORDER_STARTUP_TIMEOUT).await {
+        return Err(recorder_failed(&window, reason));
+    }
+
+    // a new session starts, forget about the flows of the previous one
+    state
+        .recording
+        .lock()
+        .unwrap()
+        .start(project_name, filter.unwrap_or_default());
 
     let window_clone = window.clone();
-    // spawn a task to receive the events from the proxy and send them to the frontend
+    // spawn a task to receive the events from the proxy, record them and send them to the frontend
     tauri::async_runtime::spawn(async move {
+        let mut last_saved = Instant::now();
         while let Some(event) = rx.recv().await {
             if let process::CommandEvent::Stdout(line) = event {
                 let line = line.trim_end();
                 println!("{:?}", line);
-                let v: Value = serde_json::from_str(line).unwrap();
+                let v: Value = match serde_json::from_str(line) {
+                    Ok(v) => v,
+                    Err(_) => {
+                        // besides flows, the proxy writes messages of its own such as its banner
+                        eprintln!("unexpected output from the recorder proxy: {}", line);
+                        continue;
+                    }
+                };
+                if let Ok(flow) = serde_json::from_value::<models::RecordedFlow>(v.clone()) {
+                    let state = window_clone.state::<ApplicationState>();
+                    let flow_id = flow.id.clone();
+                    let outcome = state.recording.lock().unwrap().record(flow);
+                    match outcome {
+                        recording::RecordOutcome::Recorded => {}
+                        recording::RecordOutcome::Filtered | recording::RecordOutcome::Paused => continue,
+                        recording::RecordOutcome::Dropped => {
+                            // the frontend already received the request, let it know it's gone
+                            window_clone.emit("browser-request-dropped", flow_id).expect("failed to send browser-request-dropped event");
+                            continue;
+                        }
+                    }
+
+                    // periodically save the session so that it survives the app being closed
+                    if last_saved.elapsed() >= RECORDING_AUTOSAVE_INTERVAL {
+                        if let Err(e) = state.save_recording() {
+                            eprintln!("failed to save the recording: {}", e);
+                        }
+                        last_saved = Instant::now();
+                    }
+                }
                 window_clone.emit("browser-request", v).expect("failed to send browser-request event");
             } else {
                 println!("{:?}", event);
@@ -151,47 +296,332 @@ async fn open_browser(handle: tauri::AppHandle, window: Window) {
     user_data_dir.push("kroco6");
     let user_data_dir = format!("--user-data-dir={}", user_data_dir.display());
 
-    let trust_certificate_fingerprint = "--ignore-certificate-errors-spki-list=pXWvAFIlMGj9EcIWKFJOpLkB6v0xCWDmz4k4T/sdu6E=";
+    let trust_certificate_fingerprint = format!("--ignore-certificate-errors-spki-list={}", certificate_authority.spki_fingerprint);
 
-    // create a channel to communicate back when the stop-recorder event is received
-    // this channel doesn't send anything, it will just be dropped to indicate the arrival of the
-    // event. Since we are not passing anything the type of the channel has to be specified.
-    let (stop_recorder_tx, mut stop_recorder_rx) = mpsc::channel::<()>(1);
+    // disable all the mentioned optimizations from chrome as they are noisy
+    // https://stackoverflow.com/questions/71017812/how-to-remove-https-optimizationguide-pa-googleapis-com-call-execution-when-th
+    let disable_optimizations = "--disable-features=OptimizationGuideModelDownloading,OptimizationHintsFetching,OptimizationTargetPrediction,OptimizationHints";
 
-    task::spawn_blocking(move || {
-        // disable all the mentioned optimizations from chrome as they are noisy
-        // https://stackoverflow.com/questions/71017812/how-to-remove-https-optimizationguide-pa-googleapis-com-call-execution-when-th
-        let disable_optimizations = "--disable-features=OptimizationGuideModelDownloading,OptimizationHintsFetching,OptimizationTargetPrediction,OptimizationHints";
+    let browser = Command::new(&browser_path)
+        .arg("--new")
+        // .arg("https://grafana.com")
+        .args(["--args", &user_data_dir, &trust_certificate_fingerprint, &proxy_server, "--hide-crash-restore-bubble", "--test-type", "--no-default-browser-check", "--no-first-run", "--disable-background-networking", "--disable-component-update", disable_optimizations])
+        .spawn()
+        .map_err(|e| recorder_failed(&window, format!("failed to launch the browser at {}: {}", browser_path.display(), e)))?;
+    recorder_session.attach_browser(browser);
 
-        let path = default_executable().expect("failed to retrieve the browser");
-        let mut command = Command::new(path)
-            .arg("--new")
-            // .arg("https://grafana.com")
-            .args(["--args", &user_data_dir, &trust_certificate_fingerprint, "--proxy-server=http://localhost:8080", "--hide-crash-restore-bubble", "--test-type", "--no-default-browser-check", "--no-first-run", "--disable-background-networking", "--disable-component-update", disable_optimizations])
-        .spawn().expect("failed to launch browser");
+    *state.recorder.lock().unwrap() = Some(recorder_session);
 
-        window.emit("browser-started", "").unwrap();
+    window.emit("browser-started", "").unwrap();
 
-        window.once("stop-recorder", move |_| {
-            // dropping the tx channel to indicate that we received the event
-            drop(stop_recorder_tx);
-        });
+    let stop_listener = window.once("stop-recorder", move |_| {
+        handle.state::<ApplicationState>().stop_recorder();
+    });
+    *state.recorder_stop_listener.lock().unwrap() = Some(stop_listener);
+
+    Ok(())
+}
+
+// Lets the frontend know that the recorder couldn't start, and why.
+//
+// Returns the reason so that it can also be returned by the command.
+fn recorder_failed(window: &Window, reason: String) -> String {
+    eprintln!("{}", reason);
+    if let Err(e) = window.emit("recorder-failed", &reason) {
+        eprintln!("failed to send recorder-failed event: {}", e);
+    }
+    reason
+}
+
+// Lists the Chromium-family browsers the recorder can use.
+#[tauri::command]
+async fn list_browsers() -> Result<Vec<models::DetectedBrowser>, String> {
+    Ok(browser::detect_browsers())
+}
+
+// Pauses the current recorder session, the flows seen while paused are dropped.
+#[tauri::command]
+async fn pause_recording(state: tauri::State<'_, ApplicationState>) -> Result<(), String> {
+    state.recording.lock().unwrap().pause();
+    Ok(())
+}
+
+#[tauri::command]
+async fn resume_recording(state: tauri::State<'_, ApplicationState>) -> Result<(), String> {
+    state.recording.lock().unwrap().unpause();
+    Ok(())
+}
+
+// Adds a marker to the current recorder session, the requests following it
+// are grouped under its label in the generated script.
+#[tauri::command]
+async fn add_recording_marker(
+    state: tauri::State<'_, ApplicationState>,
+    label: &str,
+) -> Result<models::RecordingMarker, String> {
+    let label = label.trim();
+    if label.is_empty() {
+        return Err("the marker label can't be empty".to_string());
+    }
+
+    Ok(state.recording.lock().unwrap().add_marker(label))
+}
+
+// Exports the flows captured during the current, or last, recorder
+// session as an HTTP Archive (HAR) file.
+#[tauri::command]
+async fn export_recording_har(
+    state: tauri::State<'_, ApplicationState>,
+    path: &str,
+) -> Result<(), String> {
+    let har = {
+        let recording = state.recording.lock().unwrap();
+        har::Har::from_flows(recording.flows())
+    };
+
+    har.write(Path::new(path)).map_err(|e| e.to_string())
+}
+
+// Generates a k6 script out of the flows captured during the current, or
+// last, recorder session, and saves it as a new javascript test of the project.
+#[tauri::command]
+async fn generate_script_from_recording(
+    state: tauri::State<'_, ApplicationState>,
+    project_name: &str,
+    test_name: &str,
+    options: Option<models::ScriptOptions>,
+) -> Result<models::Test, String> {
+    let script = {
+        let recording = state.recording.lock().unwrap();
+        recording::to_k6_script(recording.flows(), recording.markers(), &options.unwrap_or_default())
+    };
 
-        // wait for stop-recorder event
-        stop_recorder_rx.blocking_recv();
+    state
+        .project_manager
+        .create_test(
+            project_name,
+            models::Test::new(test_name, models::TestKind::Javascript, &script),
+        )
+        .map_err(|e| e.to_string())
+}
 
-        command.kill().expect("failed to kill the browser process");
-        child.kill().expect("failed to kill the proxy process");
+#[tauri::command]
+async fn list_recordings(
+    state: tauri::State<'_, ApplicationState>,
+    project_name: &str,
+) -> Result<Vec<models::RecordingSummary>, String> {
+    state
+        .project_manager
+        .list_recordings(project_name)
+        .map_err(|e| e.to_string())
+}
 
-        // seems like the sidecar is spawning two processes and the second one is not getting
-        // closed so we manually check for running mitmdump and kill them.
-        // Should be good enough for now.
-        let mut sys = System::new();
-        sys.refresh_processes();
-        for process in sys.processes_by_name("mitmdump") {
-            process.kill();
+// Loads a recorder session saved in a project.
+//
+// The session also becomes the current one, so that it can be
+// exported or turned into a script.
+#[tauri::command]
+async fn load_recording(
+    state: tauri::State<'_, ApplicationState>,
+    project_name: &str,
+    recording_id: &str,
+) -> Result<models::RecordingSession, String> {
+    let session = state
+        .project_manager
+        .load_recording(project_name, recording_id)
+        .map_err(|e| e.to_string())?;
+
+    state
+        .recording
+        .lock()
+        .unwrap()
+        .resume(project_name, session.clone());
+
+    Ok(session)
+}
+
+#[tauri::command]
+async fn delete_recording(
+    state: tauri::State<'_, ApplicationState>,
+    project_name: &str,
+    recording_id: &str,
+) -> Result<(), String> {
+    state
+        .project_manager
+        .delete_recording(project_name, recording_id)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn list_suites(
+    state: tauri::State<'_, ApplicationState>,
+    project_name: &str,
+) -> Result<Vec<models::Suite>, String> {
+    state
+        .project_manager
+        .list_suites(project_name)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_suite(
+    state: tauri::State<'_, ApplicationState>,
+    project_name: &str,
+    suite_name: &str,
+) -> Result<models::Suite, String> {
+    state
+        .project_manager
+        .get_suite(project_name, suite_name)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn save_suite(
+    state: tauri::State<'_, ApplicationState>,
+    project_name: &str,
+    suite: models::Suite,
+) -> Result<(), String> {
+    state
+        .project_manager
+        .save_suite(project_name, &suite)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn delete_suite(
+    state: tauri::State<'_, ApplicationState>,
+    project_name: &str,
+    suite_name: &str,
+) -> Result<(), String> {
+    state
+        .project_manager
+        .delete_suite(project_name, suite_name)
+        .map_err(|e| e.to_string())
+}
+
+// Runs the tests of a suite locally, and returns their aggregated result.
+//
+// A `suite-step-finished` event is emitted as each step finishes. Tests
+// which aren't javascript ones are run with the scripts the frontend
+// generated for them, given by test name.
+#[tauri::command]
+async fn run_suite(
+    window: Window,
+    state: tauri::State<'_, ApplicationState>,
+    project_name: &str,
+    suite_name: &str,
+    scripts: Option<HashMap<String, String>>,
+) -> Result<models::SuiteResult, String> {
+    let scripts = scripts.unwrap_or_default();
+
+    let suite = state
+        .project_manager
+        .get_suite(project_name, suite_name)
+        .map_err(|e| e.to_string())?;
+
+    let project_environment_manager = state
+        .project_manager
+        .environment_manager(project_name)
+        .map_err(|e| e.to_string())?;
+
+    let steps = suite
+        .steps
+        .iter()
+        .map(|step| {
+            let test = state
+                .project_manager
+                .get_test(project_name, &step.test_name)
+                .map_err(|e| format!("failed to load test {}: {}", step.test_name, e))?;
+            let script = match (scripts.get(&test.name), test.kind) {
+                (Some(script), _) => script.clone(),
+                (None, models::TestKind::Javascript) => test.content,
+                (None, _) => return Err(format!("no script was generated for {}", test.name)),
+            };
+
+            let variables = operations::resolve_effective_variables(
+                &state.environment_manager,
+                Some(&project_environment_manager),
+                step.options.environment.as_deref(),
+            )
+            .map_err(|e| format!("failed to resolve the environment variables: {}", e))?;
+
+            Ok(runner::PreparedStep {
+                test_name: test.name,
+                script,
+                variables,
+                args: runner::step_args(&step.options),
+            })
+        })
+        .collect();
+
+    let result = runner::run_suite(&suite, steps, |index, result| {
+        if result.status != models::StepStatus::Skipped {
+            if let Err(e) = state.project_manager.record_test_run(
+                project_name,
+                &result.test_name,
+                result.status,
+            ) {
+                eprintln!("failed to record the run of {}: {}", result.test_name, e);
+            }
         }
-    });
+
+        let event = models::SuiteStepEvent {
+            suite_name: suite.name.clone(),
+            index,
+            result: result.clone(),
+        };
+        if let Err(e) = window.emit("suite-step-finished", event) {
+            eprintln!("failed to send suite-step-finished event: {}", e);
+        }
+    })
+    .await;
+
+    Ok(result)
+}
+
+// Imports the flows of an HTTP Archive (HAR) file as a new recorder
+// session of the project.
+//
+// When a test name is provided, a javascript test replaying the
+// imported flows is created as well.
+#[tauri::command]
+async fn import_har(
+    state: tauri::State<'_, ApplicationState>,
+    path: &str,
+    project_name: &str,
+    test_name: Option<&str>,
+) -> Result<models::ImportedHar, String> {
+    let flows = har::Har::read(Path::new(path))
+        .map_err(|e| e.to_string())?
+        .to_flows();
+
+    let session = recording::imported_session(flows);
+    state
+        .project_manager
+        .save_recording(project_name, &session)
+        .map_err(|e| e.to_string())?;
+
+    let flows = session.flows;
+    let test = match test_name {
+        Some(test_name) => {
+            let script = recording::to_k6_script(&flows, &[], &models::ScriptOptions::default());
+            let test = state
+                .project_manager
+                .create_test(
+                    project_name,
+                    models::Test::new(test_name, models::TestKind::Javascript, &script),
+                )
+                .map_err(|e| e.to_string())?;
+            Some(test)
+        }
+        None => None,
+    };
+
+    Ok(models::ImportedHar {
+        recording_id: session.id,
+        flows,
+        test,
+    })
 }
 
 #[tauri::command]
@@ -224,6 +654,8 @@ async fn open_run_window(
     handle: tauri::AppHandle,
     state: tauri::State<'_, ApplicationState>,
     script: String,
+    project_name: Option<String>,
+    test_name: Option<String>,
 ) -> Result<(), String> {
     let run_window = tauri::WindowBuilder::new(
         &handle,
@@ -240,6 +672,9 @@ async fn open_run_window(
     state_script.push_str(script.as_str());
     println!("{:?}", state_script);
 
+    *state.script_project.lock().unwrap() = project_name;
+    *state.script_test.lock().unwrap() = test_name;
+
     Ok(())
 }
 
@@ -299,6 +734,25 @@ async fn save_project_config(
         .map_err(|e| e.to_string())
 }
 
+// Validates a cloud configuration against the cloud API before it gets saved.
+//
+// When no cloud token is provided, the one that would otherwise be used for
+// the project is validated.
+#[tauri::command]
+async fn validate_cloud_config(
+    state: tauri::State<'_, ApplicationState>,
+    project_name: Option<&str>,
+    cloud_token: Option<String>,
+    cloud_project_id: &str,
+) -> Result<models::CloudConfigValidation, String> {
+    let cloud_token = match cloud_token.filter(|token| !token.is_empty()) {
+        Some(cloud_token) => cloud_token,
+        None => state.cloud_credentials(project_name)?.token,
+    };
+
+    cloud::validate_cloud_config(&cloud_token, cloud_project_id).await
+}
+
 #[tauri::command]
 async fn load_environments(
     state: tauri::State<'_, ApplicationState>,
@@ -317,6 +771,229 @@ async fn save_environments(
         .map_err(|e| e.to_string())
 }
 
+#[tauri::command]
+async fn create_environment(
+    state: tauri::State<'_, ApplicationState>,
+    project_name: Option<&str>,
+    name: &str,
+    description: Option<&str>,
+) -> Result<models::EnvironmentsData, String> {
+    state.update_environments(project_name, |environment_manager| {
+        environment_manager.create_environment(name.trim(), description.unwrap_or_default())
+    })
+}
+
+#[tauri::command]
+async fn rename_environment(
+    state: tauri::State<'_, ApplicationState>,
+    project_name: Option<&str>,
+    name: &str,
+    new_name: &str,
+) -> Result<models::EnvironmentsData, String> {
+    state.update_environments(project_name, |environment_manager| {
+        environment_manager.rename_environment(name, new_name.trim())
+    })
+}
+
+#[tauri::command]
+async fn delete_environment(
+    state: tauri::State<'_, ApplicationState>,
+    project_name: Option<&str>,
+    name: &str,
+) -> Result<models::EnvironmentsData, String> {
+    state.update_environments(project_name, |environment_manager| {
+        environment_manager.delete_environment(name)
+    })
+}
+
+// Sets a variable of an environment, secret values are moved to the secret store.
+#[tauri::command]
+async fn set_variable(
+    state: tauri::State<'_, ApplicationState>,
+    project_name: Option<&str>,
+    environment_name: &str,
+    name: &str,
+    value: &str,
+    secret: Option<bool>,
+) -> Result<models::EnvironmentsData, String> {
+    let variable = models::EnvironmentVariable::new(value, secret.unwrap_or(false));
+    state.update_environments(project_name, |environment_manager| {
+        environment_manager.set_variable(environment_name, name.trim(), &variable)
+    })
+}
+
+#[tauri::command]
+async fn unset_variable(
+    state: tauri::State<'_, ApplicationState>,
+    project_name: Option<&str>,
+    environment_name: &str,
+    name: &str,
+) -> Result<models::EnvironmentsData, String> {
+    state.update_environments(project_name, |environment_manager| {
+        environment_manager.unset_variable(environment_name, name)
+    })
+}
+
+// Sets the active environment, globally or for a project.
+//
+// A project can select one of its own environments or a global one, or
+// clear its selection with an empty name to fall back to the global one.
+#[tauri::command]
+async fn set_active_environment(
+    state: tauri::State<'_, ApplicationState>,
+    project_name: Option<&str>,
+    name: &str,
+) -> Result<models::EnvironmentsData, String> {
+    let inherited = match project_name {
+        Some(_) => state.environment_manager.environment_names().map_err(|e| e.to_string())?,
+        None => vec![],
+    };
+
+    state.update_environments(project_name, |environment_manager| {
+        environment_manager.set_active_environment(name, &inherited)
+    })
+}
+
+// Imports an environment from a `.env` file, a JSON object of variables
+// or a Postman environment export, globally or into a project.
+//
+// The variables are added to the environment with the given name, or
+// else to the one named after the file, which is created if needed.
+#[tauri::command]
+async fn import_environment(
+    state: tauri::State<'_, ApplicationState>,
+    path: &str,
+    project_name: Option<&str>,
+    environment_name: Option<&str>,
+) -> Result<models::EnvironmentsData, String> {
+    let path = Path::new(path);
+    let imported = environment_files::read(path).map_err(|e| e.to_string())?;
+
+    let name = environment_name
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .or(imported.name)
+        .or_else(|| environment_files::environment_name(path))
+        .ok_or("missing environment name".to_string())?;
+
+    state.update_environments(project_name, |environment_manager| {
+        environment_manager.import_environment(&name, "", &imported.variables)
+    })
+}
+
+// Exports the variables of an environment to a `.env` or JSON file, the
+// format being inferred from the file's extension when not provided.
+//
+// Secret variables are left out unless explicitly included.
+#[tauri::command]
+async fn export_environment(
+    state: tauri::State<'_, ApplicationState>,
+    path: &str,
+    project_name: Option<&str>,
+    environment_name: &str,
+    format: Option<environment_files::EnvironmentFileFormat>,
+    include_secrets: Option<bool>,
+) -> Result<(), String> {
+    let path = Path::new(path);
+    let format = format.unwrap_or_else(|| environment_files::EnvironmentFileFormat::from_path(path));
+
+    let include_secrets = include_secrets.unwrap_or(false);
+    state.with_environment_manager(project_name, |environment_manager| {
+        let variables = environment_manager.export_variables(environment_name, include_secrets)?;
+        environment_files::write(path, &variables, format, include_secrets)
+    })
+}
+
+// Loads the environments defined by a project, which take precedence
+// over the global ones.
+#[tauri::command]
+async fn load_project_environments(
+    state: tauri::State<'_, ApplicationState>,
+    project_name: &str,
+) -> Result<models::EnvironmentsData, String> {
+    state
+        .project_manager
+        .environment_manager(project_name)
+        .and_then(|environment_manager| environment_manager.load())
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn save_project_environments(
+    state: tauri::State<'_, ApplicationState>,
+    project_name: &str,
+    environments_data: models::EnvironmentsData,
+) -> Result<(), String> {
+    state
+        .project_manager
+        .environment_manager(project_name)
+        .and_then(|environment_manager| environment_manager.save(&environments_data))
+        .map_err(|e| e.to_string())
+}
+
+// Loads the environments in effect for a project: the global ones,
+// overridden by the ones of the project.
+#[tauri::command]
+async fn load_effective_environments(
+    state: tauri::State<'_, ApplicationState>,
+    project_name: &str,
+) -> Result<models::EnvironmentsData, String> {
+    let global = state.environment_manager.load().map_err(|e| e.to_string())?;
+    let project = state
+        .project_manager
+        .environment_manager(project_name)
+        .and_then(|environment_manager| environment_manager.load())
+        .map_err(|e| e.to_string())?;
+
+    Ok(operations::merge_environments(global, project))
+}
+
+// Returns the variables in effect for a project in the given environment,
+// or in the active one, along with the scope each of them comes from.
+//
+// The values of secret variables are masked.
+#[tauri::command]
+async fn get_effective_variables(
+    state: tauri::State<'_, ApplicationState>,
+    project_name: &str,
+    environment_name: Option<&str>,
+) -> Result<BTreeMap<String, models::ResolvedVariable>, String> {
+    let global = state.environment_manager.load().map_err(|e| e.to_string())?;
+    let project = state
+        .project_manager
+        .environment_manager(project_name)
+        .and_then(|environment_manager| environment_manager.load())
+        .map_err(|e| e.to_string())?;
+
+    let environment_name =
+        operations::effective_environment_name(environment_name, &project.active, &global.active);
+
+    operations::effective_variables(&global, &project, &environment_name).map_err(|e| e.to_string())
+}
+
+// Returns the environment in effect for a project, the active one unless
+// given, with the references its values hold resolved, for generating scripts.
+//
+// The values of secrets, and of the variables derived from them, are masked.
+#[tauri::command]
+async fn get_interpolated_environment(
+    state: tauri::State<'_, ApplicationState>,
+    project_name: &str,
+    environment_name: Option<&str>,
+) -> Result<models::Environment, String> {
+    let global = state.environment_manager.load().map_err(|e| e.to_string())?;
+    let project = state
+        .project_manager
+        .environment_manager(project_name)
+        .and_then(|environment_manager| environment_manager.load())
+        .map_err(|e| e.to_string())?;
+
+    let environment_name =
+        operations::effective_environment_name(environment_name, &project.active, &global.active);
+
+    operations::interpolated_environment(&global, &project, &environment_name).map_err(|e| e.to_string())
+}
+
 #[tauri::command]
 async fn create_test(
     state: tauri::State<'_, ApplicationState>,
@@ -329,14 +1006,17 @@ async fn create_test(
         .map_err(|e| e.to_string())
 }
 
+// Lists the tests of a project, only keeping those labelled
+// with the given tag when one is provided.
 #[tauri::command]
 async fn list_tests(
     state: tauri::State<'_, ApplicationState>,
     project_name: &str,
+    tag: Option<&str>,
 ) -> Result<Vec<models::Test>, String> {
     state
         .project_manager
-        .list_tests(project_name)
+        .list_tests(project_name, tag)
         .map_err(|e| e.to_string())
 }
 
@@ -377,6 +1057,31 @@ async fn save_test(
         .map_err(|e| e.to_string())
 }
 
+#[tauri::command]
+async fn get_test_metadata(
+    state: tauri::State<'_, ApplicationState>,
+    project_name: &str,
+    test_name: &str,
+) -> Result<models::TestMetadata, String> {
+    state
+        .project_manager
+        .get_test_metadata(project_name, test_name)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn update_test_metadata(
+    state: tauri::State<'_, ApplicationState>,
+    project_name: &str,
+    test_name: &str,
+    update: models::TestMetadataUpdate,
+) -> Result<models::TestMetadata, String> {
+    state
+        .project_manager
+        .update_test_metadata(project_name, test_name, update)
+        .map_err(|e| e.to_string())
+}
+
 #[tauri::command]
 async fn run_script(state: tauri::State<'_, ApplicationState>) -> Result<String, String> {
 
@@ -385,33 +1090,42 @@ async fn run_script(state: tauri::State<'_, ApplicationState>) -> Result<String,
         state_script.clone()
     };
 
-    let k6_executable = executable::get_executable_path();
-
-    // TODO: make it toggable
-    std::env::set_var("K6_WEB_DASHBOARD", "true");
-    let mut child = Command::new(k6_executable)
-        .arg("run")
-        .arg("-")
-        .stdin(Stdio::piped())
-        .stdout(Stdio::piped())
-        .spawn()
-        .expect("Failed to execute command");
-
-    if let Some(mut stdin) = child.stdin.take() {
-        stdin
-            .write_all(script.as_bytes())
-            .expect("Failed to write script.");
-    }
+    let project_name = state.script_project.lock().unwrap().clone();
+    let project_environment_manager = match &project_name {
+        Some(project_name) => Some(
+            state
+                .project_manager
+                .environment_manager(project_name)
+                .map_err(|e| e.to_string())?,
+        ),
+        None => None,
+    };
 
-    let mut k6_output = String::new();
-    if let Some(mut stdout) = child.stdout.take() {
-        stdout
-            .read_to_string(&mut k6_output)
-            .expect("Failed to read stdout");
+    // the variables of the active environment, secrets included, are exposed to the
+    // script through `__ENV`, see runner::run_k6.
+    let variables = operations::resolve_effective_variables(
+        &state.environment_manager,
+        project_environment_manager.as_ref(),
+        None,
+    )
+    .map_err(|e| format!("failed to resolve the environment variables: {}", e))?;
+
+    // TODO: make the web dashboard toggable
+    let run = runner::run_k6(&script, &variables, &[], true)
+        .map_err(|e| format!("failed to run k6: {}", e))?;
+
+    let test_name = state.script_test.lock().unwrap().clone();
+    if let (Some(project_name), Some(test_name)) = (&project_name, &test_name) {
+        let status = match run.exit_code {
+            Some(0) => models::StepStatus::Passed,
+            _ => models::StepStatus::Failed,
+        };
+        if let Err(e) = state.project_manager.record_test_run(project_name, test_name, status) {
+            eprintln!("failed to record the run of {}: {}", test_name, e);
+        }
     }
 
-    let _ = child.wait_with_output().expect("Failed to execute command");
-    Ok(k6_output)
+    Ok(run.output)
 }
 
 // ApplicationState holds the state of the application.
@@ -430,12 +1144,54 @@ struct ApplicationState {
     // The environment manager used to interact with environments
     pub environment_manager: operations::EnvironmentManager,
 
+    // The store used to keep secrets, such as cloud tokens, out of
+    // plaintext configuration files
+    pub secret_store: Arc<dyn SecretStore>,
+
     // Legacy: the script to run
     script: Mutex<String>,
+
+    // The project the script to run belongs to, if any, whose
+    // environments apply to the run
+    script_project: Mutex<Option<String>>,
+
+    // The saved test of the project the script to run is, if any, whose
+    // metadata records the status of the run
+    script_test: Mutex<Option<String>>,
+
+    // The cloud test runs that are currently in progress, indexed by test run id.
+    cloud_runs: Mutex<HashMap<u32, CloudRunProcess>>,
+
+    // The flows captured during the current, or last, recorder session
+    recording: Mutex<recording::Recording>,
+
+    // The processes of the running recorder session, if any
+    recorder: Mutex<Option<recorder::RecorderSession>>,
+
+    // The listener stopping the running recorder session on request, which
+    // must not outlive the session it was registered for
+    recorder_stop_listener: Mutex<Option<tauri::EventHandler>>,
+
+    // The directory holding the certificate authority of the recorder
+    certificates_path: PathBuf,
+
+    // Keeps other instances of the app from changing the stored files
+    _storage_lock: storage::StorageLock,
+}
+
+// A cloud test run started from the app, which is still in progress
+struct CloudRunProcess {
+    // The k6 process attached to the run
+    child: Child,
+
+    // The cloud token the run was started with, which is also
+    // the one able to stop it
+    cloud_token: String,
 }
 
 impl ApplicationState {
-    pub fn new() -> Self {
+    // Fails if the storage directory is locked by another instance of the application.
+    pub fn new() -> Result<Self, String> {
         // We obtain the system's configuration directory
         // from the `dirs` crate.
         let config_dir = dirs::config_dir().expect("Failed to get config directory");
@@ -447,66 +1203,474 @@ impl ApplicationState {
             fs::create_dir(&storage_path).expect("Failed to create storage directory");
         }
 
-        Self {
+        let storage_lock = storage::StorageLock::acquire(&storage_path)
+            .map_err(|e| format!("Failed to lock the storage directory: {}", e))?;
+
+        let secret_store = secrets::default_secret_store(&storage_path)
+            .map_err(|e| format!("Failed to open the secret store: {}", e))?;
+
+        Ok(Self {
             // storage_path: storage_path.clone(),
-            project_manager: operations::LocalProjectManager::new(storage_path.clone()),
-            environment_manager: operations::EnvironmentManager::new(storage_path.clone()),
+            project_manager: operations::LocalProjectManager::new(
+                storage_path.clone(),
+                secret_store.clone(),
+            ),
+            environment_manager: operations::EnvironmentManager::new(
+                storage_path.clone(),
+                secret_store.clone(),
+            ),
+            secret_store,
             script: Mutex::new(String::new()),
+            script_project: Mutex::new(None),
+            script_test: Mutex::new(None),
+            cloud_runs: Mutex::new(HashMap::new()),
+            recording: Mutex::new(recording::Recording::new()),
+            recorder: Mutex::new(None),
+            recorder_stop_listener: Mutex::new(None),
+            certificates_path: storage_path.join("certificates"),
+            _storage_lock: storage_lock,
+        })
+    }
+}
+
+impl ApplicationState {
+    // Resolves the cloud credentials to use for the given project, or
+    // the app-wide ones if no project is provided.
+    //
+    // See cloud::resolve_cloud_token for the order in which the cloud
+    // token is resolved.
+    fn cloud_credentials(
+        &self,
+        project_name: Option<&str>,
+    ) -> Result<models::CloudCredentials, String> {
+        let project_config = match project_name {
+            Some(project_name) => match self.project_manager.load_project_config(project_name) {
+                Ok(project_config) => Some(project_config),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+                Err(e) => return Err(e.to_string()),
+            },
+            None => None,
+        };
+
+        let token = cloud::resolve_cloud_token(project_config.as_ref(), self.secret_store.as_ref())
+            .map_err(|e| e.to_string())?
+            .ok_or("missing cloud token".to_string())?;
+
+        let project_id = project_config
+            .and_then(|project_config| project_config.cloud_project_id)
+            .filter(|project_id| !project_id.is_empty());
+
+        Ok(models::CloudCredentials { token, project_id })
+    }
+}
+
+impl ApplicationState {
+    // Applies a change to the environments of the given project, or to the
+    // global ones, and returns the environments as they are after the change.
+    fn update_environments(
+        &self,
+        project_name: Option<&str>,
+        change: impl FnOnce(&operations::EnvironmentManager) -> std::io::Result<()>,
+    ) -> Result<models::EnvironmentsData, String> {
+        self.with_environment_manager(project_name, |environment_manager| {
+            change(environment_manager).and_then(|_| environment_manager.load())
+        })
+    }
+
+    // Runs an operation on the environments of the given project, or on
+    // the global ones.
+    fn with_environment_manager<T>(
+        &self,
+        project_name: Option<&str>,
+        operation: impl FnOnce(&operations::EnvironmentManager) -> std::io::Result<T>,
+    ) -> Result<T, String> {
+        let project_environment_manager;
+        let environment_manager = match project_name {
+            Some(project_name) => {
+                project_environment_manager = self
+                    .project_manager
+                    .environment_manager(project_name)
+                    .map_err(|e| e.to_string())?;
+                &project_environment_manager
+            }
+            None => &self.environment_manager,
+        };
+
+        operation(environment_manager).map_err(|e| e.to_string())
+    }
+
+    // Stops the running recorder session, if any, and saves what
+    // it recorded to its project.
+    fn stop_recorder(&self) {
+        let recorder_session = self.recorder.lock().unwrap().take();
+        if let Some(mut recorder_session) = recorder_session {
+            recorder_session.stop();
+
+            if let Err(e) = self.save_recording() {
+                eprintln!("failed to save the recording: {}", e);
+            }
         }
     }
 
-    pub fn default() -> Self {
-        Self::new()
+    // Saves the current recorder session to its project, if it has one.
+    fn save_recording(&self) -> Result<(), String> {
+        let (project_name, session) = {
+            let recording = self.recording.lock().unwrap();
+            match recording.project_name() {
+                Some(project_name) => (project_name.to_string(), recording.to_session()),
+                None => return Ok(()),
+            }
+        };
+
+        self.project_manager
+            .save_recording(&project_name, &session)
+            .map_err(|e| e.to_string())
     }
 }
 
 #[tauri::command]
-async fn get_cloud_token() -> Result<String, String> {
-    Ok(std::env::var(String::from("K6_CLOUD_TOKEN")).unwrap_or(String::from("")))
+async fn get_cloud_token(state: tauri::State<'_, ApplicationState>) -> Result<String, String> {
+    let cloud_token = cloud::resolve_cloud_token(None, state.secret_store.as_ref())
+        .map_err(|e| e.to_string())?;
+
+    Ok(cloud_token.unwrap_or(String::from("")))
 }
 
 #[tauri::command]
-fn set_cloud_token(token: String) -> Result<(), String> {
-    std::env::set_var("K6_CLOUD_TOKEN", token);
-    Ok(())
+fn set_cloud_token(state: tauri::State<'_, ApplicationState>, token: String) -> Result<(), String> {
+    if token.is_empty() {
+        return state
+            .secret_store
+            .delete(cloud::CLOUD_TOKEN_SECRET_KEY)
+            .map_err(|e| e.to_string());
+    }
+
+    state
+        .secret_store
+        .set(cloud::CLOUD_TOKEN_SECRET_KEY, &token)
+        .map_err(|e| e.to_string())
 }
 
 #[tauri::command]
-async fn run_script_in_cloud(script: String, project_id: String) -> Result<String, String> {
+async fn run_script_in_cloud(
+    window: Window,
+    state: tauri::State<'_, ApplicationState>,
+    script: String,
+    project_name: Option<String>,
+    project_id: Option<String>,
+) -> Result<models::CloudRun, String> {
+    let cloud_credentials = state.cloud_credentials(project_name.as_deref())?;
+    let cloud_token = cloud_credentials.token;
+
+    // An explicitly provided project id takes precedence over the project's config
+    let project_id = project_id
+        .filter(|project_id| !project_id.is_empty())
+        .or(cloud_credentials.project_id)
+        .ok_or("missing cloud project id".to_string())?;
+
+    let project_environment_manager = match &project_name {
+        Some(project_name) => Some(
+            state
+                .project_manager
+                .environment_manager(project_name)
+                .map_err(|e| e.to_string())?,
+        ),
+        None => None,
+    };
+
+    // the variables of the active environment are exposed to the script
+    // through `__ENV` like they are for local runs, secrets excepted
+    let variables = operations::resolve_effective_variables(
+        &state.environment_manager,
+        project_environment_manager.as_ref(),
+        None,
+    )
+    .map_err(|e| format!("failed to resolve the environment variables: {}", e))?;
+    let variables = cloud_run_variables(&script, variables)?;
+
+    let start_token = cloud_token.clone();
+    let (child, cloud_run) = task::spawn_blocking(move || {
+        start_cloud_run(&script, &variables, &start_token, &project_id)
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+
+    // The k6 child process stays attached to the cloud run until it completes,
+    // we keep track of it so that it can be reaped once the run is over.
+    state.cloud_runs.lock().unwrap().insert(
+        cloud_run.test_run_id,
+        CloudRunProcess {
+            child,
+            cloud_token: cloud_token.clone(),
+        },
+    );
+
+    let test_run_id = cloud_run.test_run_id;
+    tauri::async_runtime::spawn(async move {
+        follow_cloud_run(window, cloud_token, test_run_id).await;
+    });
+
+    Ok(cloud_run)
+}
+
+// Returns the variables to send along with a script ran in the cloud.
+//
+// Unlike local runs, the variables have to be passed with `--env`: k6 doesn't
+// forward its own environment to the cloud, only the `--env` values are sent
+// along with the script. Secrets would then show up in the command line of
+// k6 for the whole run, so they are left out, and scripts using them can't
+// be ran in the cloud.
+fn cloud_run_variables(
+    script: &str,
+    variables: BTreeMap<String, models::EnvironmentVariable>,
+) -> Result<BTreeMap<String, String>, String> {
+    let (secrets, variables): (BTreeMap<_, _>, BTreeMap<_, _>) =
+        variables.into_iter().partition(|(_, variable)| variable.secret);
+
+    let used_secrets: Vec<&str> = secrets
+        .keys()
+        .map(String::as_str)
+        .filter(|name| script.contains(name))
+        .collect();
+    if !used_secrets.is_empty() {
+        return Err(format!(
+            "secret variables can't be sent to cloud runs, and the script uses {}",
+            used_secrets.join(", ")
+        ));
+    }
+
+    Ok(variables
+        .into_iter()
+        .map(|(name, variable)| (name, variable.value))
+        .collect())
+}
+
+// Starts a cloud test run with k6, and waits for the cloud to acknowledge it.
+//
+// Returns the k6 process, which stays attached to the run until it
+// completes, along with the run. This blocks while k6 uploads the script.
+//
+// The variables are passed with `--env`, see cloud_run_variables.
+fn start_cloud_run(
+    script: &str,
+    variables: &BTreeMap<String, String>,
+    cloud_token: &str,
+    project_id: &str,
+) -> Result<(Child, models::CloudRun), String> {
+    let env_args = variables
+        .iter()
+        .flat_map(|(name, value)| ["--env".to_string(), format!("{}={}", name, value)]);
+
     let k6_executable = executable::get_executable_path();
     let mut child = Command::new(k6_executable)
         .arg("cloud")
+        .args(env_args)
         .arg("-")
         .stdin(Stdio::piped())
         .stdout(Stdio::piped())
-        .env(
-            "K6_CLOUD_TOKEN",
-            std::env::var(String::from("K6_CLOUD_TOKEN")).unwrap_or(String::from("")),
-        )
+        .env("K6_CLOUD_TOKEN", cloud_token)
         .env("K6_CLOUD_PROJECT_ID", project_id)
         .env("K6_CLOUD_NAME", "kroco6 script.js")
         .spawn()
-        .expect("Failed to execute command");
+        .map_err(|e| format!("failed to run k6: {}", e))?;
+
+    match read_cloud_run(&mut child, script) {
+        Ok(cloud_run) => Ok((child, cloud_run)),
+        Err(e) => {
+            // k6 failed to start the run, don't leave it behind
+            let _ = child.kill();
+            let _ = child.wait();
+            Err(e)
+        }
+    }
+}
 
+// Sends the script to k6, and reads the cloud test run it started from its output.
+fn read_cloud_run(child: &mut Child, script: &str) -> Result<models::CloudRun, String> {
     if let Some(mut stdin) = child.stdin.take() {
         stdin
             .write_all(script.as_bytes())
-            .expect("Failed to write script.");
+            .map_err(|e| format!("failed to send the script to k6: {}", e))?;
+    }
+
+    let stdout = match child.stdout.take() {
+        Some(stdout) => stdout,
+        None => return Err("Failed to read k6 output".to_string()),
+    };
+
+    let url_re = Regex::new(r"output: (https?://[^\s]+)").unwrap();
+    let test_run_id_re = Regex::new(r"/runs/(\d+)").unwrap();
+
+    let mut reader = BufReader::new(stdout);
+    let mut cloud_run = None;
+    let mut line = String::new();
+    loop {
+        line.clear();
+        let read = reader
+            .read_line(&mut line)
+            .map_err(|e| format!("failed to read k6 output: {}", e))?;
+        if read == 0 {
+            break;
+        }
+
+        if let Some(cap) = url_re.captures(&line) {
+            let url = cap[1].to_string();
+            let test_run_id = test_run_id_re
+                .captures(&url)
+                .and_then(|cap| cap[1].parse::<u32>().ok())
+                .ok_or(format!("No test run id found in {}", url))?;
+
+            cloud_run = Some(models::CloudRun { test_run_id, url });
+            break;
+        }
     }
 
-    let re = Regex::new(r"output: (https?://[^\s]+)").unwrap();
-    if let Some(stdout) = child.stdout.take() {
-        let reader = BufReader::new(stdout);
-        for line in reader.lines() {
-            let line = line.expect("Failed to read line");
-            if let Some(cap) = re.captures(&line) {
-                return Ok(cap[1].to_string());
+    let cloud_run = cloud_run.ok_or("No URL found in output".to_string())?;
+
+    // k6 keeps reporting the progress of the run on its standard output
+    // until it completes, so we need to keep draining it to prevent it from
+    // blocking on a full pipe.
+    std::thread::spawn(move || io::copy(&mut reader, &mut io::sink()));
+
+    Ok(cloud_run)
+}
+
+// Stops a cloud test run.
+//
+// Runs started from the app are stopped with the cloud token they were
+// started with, other runs with the token resolved for the given project.
+#[tauri::command]
+async fn stop_cloud_test_run(
+    state: tauri::State<'_, ApplicationState>,
+    test_run_id: u32,
+    project_name: Option<String>,
+) -> Result<(), String> {
+    let started_with = state
+        .cloud_runs
+        .lock()
+        .unwrap()
+        .get(&test_run_id)
+        .map(|cloud_run| cloud_run.cloud_token.clone());
+    let cloud_token = match started_with {
+        Some(cloud_token) => cloud_token,
+        None => state.cloud_credentials(project_name.as_deref())?.token,
+    };
+
+    cloud::stop_test_run(&cloud_token, test_run_id).await?;
+
+    // Once the run is stopped in the cloud the attached k6 process notices
+    // it and exits on its own, we only kill it if it doesn't within the grace period.
+    let cloud_run = state.cloud_runs.lock().unwrap().remove(&test_run_id);
+    if let Some(cloud_run) = cloud_run {
+        task::spawn_blocking(move || {
+            terminate_gracefully(cloud_run.child, CLOUD_RUN_STOP_GRACE_PERIOD)
+        });
+    }
+
+    Ok(())
+}
+
+// Waits for the child process to exit for at most `grace_period`, and kills it
+// if it is still running afterwards.
+fn terminate_gracefully(mut child: Child, grace_period: Duration) {
+    let deadline = Instant::now() + grace_period;
+    while Instant::now() < deadline {
+        match child.try_wait() {
+            Ok(None) => std::thread::sleep(Duration::from_millis(100)),
+            _ => return,
+        }
+    }
+
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+// Follows the progress of a cloud test run by polling the cloud API, and
+// emits a `cloud-run-status` event every time its status changes, until
+// the run completes.
+//
+// When the API keeps failing, the run is followed through the k6 process
+// attached to it instead, whose exit code tells how the run ended.
+async fn follow_cloud_run(window: Window, cloud_token: String, test_run_id: u32) {
+    let mut last_status = None;
+    let mut failed_polls = 0;
+
+    loop {
+        let status = match cloud::get_test_run(&cloud_token, test_run_id).await {
+            Ok(test_run) => {
+                failed_polls = 0;
+                models::CloudRunStatus::from_test_run(&test_run)
             }
+            Err(e) => {
+                failed_polls += 1;
+                match cloud_run_process_status(&window, test_run_id) {
+                    Some(status) => status,
+                    None if failed_polls >= CLOUD_RUN_MAX_FAILED_POLLS => {
+                        eprintln!("failed to get the status of cloud run {}: {}", test_run_id, e);
+                        wait_for_cloud_run_process(&window, test_run_id).await
+                    }
+                    None => {
+                        tokio::time::sleep(CLOUD_RUN_POLL_INTERVAL).await;
+                        continue;
+                    }
+                }
+            }
+        };
+
+        if last_status.as_ref() != Some(&status) {
+            let event = models::CloudRunStatusEvent {
+                test_run_id,
+                status: status.clone(),
+            };
+            if let Err(e) = window.emit("cloud-run-status", event) {
+                eprintln!("failed to send cloud-run-status event: {}", e);
+            }
+        }
+
+        if status.is_terminal() {
+            break;
         }
+
+        last_status = Some(status);
+        tokio::time::sleep(CLOUD_RUN_POLL_INTERVAL).await;
+    }
+
+    let state = window.state::<ApplicationState>();
+    let cloud_run = state.cloud_runs.lock().unwrap().remove(&test_run_id);
+    if let Some(mut cloud_run) = cloud_run {
+        // the run is over, k6 exits on its own shortly after
+        task::spawn_blocking(move || cloud_run.child.wait());
     }
+}
+
+// Returns the status the given cloud test run ended with according to the
+// k6 process attached to it, or None while the process is still running.
+fn cloud_run_process_status(window: &Window, test_run_id: u32) -> Option<models::CloudRunStatus> {
+    let state = window.state::<ApplicationState>();
+    let mut cloud_runs = state.cloud_runs.lock().unwrap();
+    let cloud_run = match cloud_runs.get_mut(&test_run_id) {
+        Some(cloud_run) => cloud_run,
+        // the process is only let go of once the run was stopped from the app
+        None => return Some(models::CloudRunStatus::AbortedByUser),
+    };
 
-    let _ = child.wait_with_output().expect("Failed to execute command");
-    Err("No URL found in output".to_string())
+    match cloud_run.child.try_wait() {
+        Ok(None) => None,
+        Ok(Some(exit_status)) => Some(models::CloudRunStatus::from_exit_code(exit_status.code())),
+        Err(_) => Some(models::CloudRunStatus::Failed),
+    }
+}
+
+// Waits for the k6 process attached to the given cloud test run to exit, and
+// returns the status the run ended with.
+async fn wait_for_cloud_run_process(window: &Window, test_run_id: u32) -> models::CloudRunStatus {
+    loop {
+        if let Some(status) = cloud_run_process_status(window, test_run_id) {
+            return status;
+        }
+
+        tokio::time::sleep(CLOUD_RUN_POLL_INTERVAL).await;
+    }
 }
 
 #[tauri::command]
@@ -1,6 +1,8 @@
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 use std::fmt::Display;
+use std::path::PathBuf;
 use std::str::FromStr;
 
 // TestKind represents the kind of test we are dealing with
@@ -55,6 +57,10 @@ pub struct Test {
     // This is stored as a string under the hood for convenience.
     // The default value is "".
     pub content: String,
+
+    // The metadata of the test, as stored next to it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<TestMetadata>,
 }
 
 impl Test {
@@ -63,23 +69,269 @@ impl Test {
             name: name.to_string(),
             kind,
             content: content.to_string(),
+            metadata: None,
             // file_path: PathBuf::new(),
         }
     }
 }
 
+// TestSummary describes a test of a project, without its content.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct TestSummary {
+    pub name: String,
+    pub kind: TestKind,
+
+    // When the test was last saved
+    pub last_modified: Option<DateTime<Utc>>,
+
+    pub tags: Vec<String>,
+}
+
+// TestMetadata holds what is known about a test besides its content.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct TestMetadata {
+    #[serde(default)]
+    pub description: String,
+
+    // The tags the test is labelled with, sorted and without duplicates
+    #[serde(default)]
+    pub tags: Vec<String>,
+
+    #[serde(default)]
+    pub owner: Option<String>,
+
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+
+    // The status of the last run of the test, locally or as part of a suite
+    #[serde(default)]
+    pub last_run_status: Option<StepStatus>,
+
+    #[serde(default)]
+    pub last_run_at: Option<DateTime<Utc>>,
+}
+
+impl TestMetadata {
+    pub fn new(created_at: DateTime<Utc>) -> Self {
+        Self {
+            description: String::new(),
+            tags: vec![],
+            owner: None,
+            created_at,
+            updated_at: created_at,
+            last_run_status: None,
+            last_run_at: None,
+        }
+    }
+
+    // Applies the changes of an update, leaving out the fields it doesn't set
+    pub fn apply(&mut self, update: TestMetadataUpdate) {
+        if let Some(description) = update.description {
+            self.description = description;
+        }
+        if let Some(tags) = update.tags {
+            self.tags = tags
+                .iter()
+                .map(|tag| tag.trim().to_string())
+                .filter(|tag| !tag.is_empty())
+                .collect::<BTreeSet<_>>()
+                .into_iter()
+                .collect();
+        }
+        if let Some(owner) = update.owner {
+            let owner = owner.trim();
+            self.owner = (!owner.is_empty()).then(|| owner.to_string());
+        }
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t.eq_ignore_ascii_case(tag.trim()))
+    }
+}
+
+// The changes made to the metadata of a test, unset fields being left as is
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct TestMetadataUpdate {
+    #[serde(default)]
+    pub description: Option<String>,
+
+    #[serde(default)]
+    pub tags: Option<Vec<String>>,
+
+    // An empty owner clears it
+    #[serde(default)]
+    pub owner: Option<String>,
+}
+
 // A Collection represents either a single test, or
 // a suite of tests (many tests meant to be ran sequentially
 // or in parallel).
-// FIXME @oleiade: put this to use and drop the dead_code allowance
-#[allow(dead_code)]
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum TestCollection {
     // A single test
-    Test(Test),
+    Test(TestSummary),
 
     // A suite of tests
-    Suite(Vec<Test>),
+    Suite(Suite),
+}
+
+// A Suite is an ordered list of tests of a project, which are ran
+// together and whose results are aggregated.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Suite {
+    pub name: String,
+
+    // How the steps of the suite are ran
+    #[serde(default)]
+    pub mode: SuiteMode,
+
+    pub steps: Vec<SuiteStep>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum SuiteMode {
+    // One step after the other, in order
+    #[default]
+    Sequential,
+
+    // All the steps at once
+    Parallel,
+}
+
+// A step of a suite: a reference to a test of the project, along with
+// the options it is ran with.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SuiteStep {
+    // The name of the test to run
+    pub test_name: String,
+
+    #[serde(default)]
+    pub options: StepOptions,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct StepOptions {
+    // The environment the test runs in, instead of the active one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub environment: Option<String>,
+
+    // Overrides of the test's own load options
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vus: Option<u32>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub iterations: Option<u32>,
+
+    // Whether the next steps of a sequential suite still run when
+    // this one fails
+    #[serde(default)]
+    pub continue_on_failure: bool,
+}
+
+// The outcome of a step of a suite run
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum StepStatus {
+    // k6 exited successfully, the test's thresholds and checks passed
+    Passed,
+
+    // k6 exited with an error, such as a crossed threshold
+    Failed,
+
+    // The step couldn't be ran, see its error
+    Error,
+
+    // The step wasn't ran, as an earlier step of the suite failed
+    Skipped,
+}
+
+// The result of a step of a suite run
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct StepResult {
+    pub test_name: String,
+    pub status: StepStatus,
+
+    // The exit code of k6, if it ran
+    pub exit_code: Option<i32>,
+
+    // How long the step ran for, in seconds
+    pub duration: f64,
+
+    // The output of k6, if it ran
+    pub output: String,
+
+    // Why the step couldn't be ran, if it errored
+    pub error: Option<String>,
+}
+
+impl StepResult {
+    pub fn skipped(test_name: &str) -> Self {
+        Self {
+            test_name: test_name.to_string(),
+            status: StepStatus::Skipped,
+            exit_code: None,
+            duration: 0.0,
+            output: String::new(),
+            error: None,
+        }
+    }
+
+    pub fn error(test_name: &str, error: &str) -> Self {
+        Self {
+            status: StepStatus::Error,
+            error: Some(error.to_string()),
+            ..Self::skipped(test_name)
+        }
+    }
+}
+
+// The aggregated result of a suite run
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SuiteResult {
+    pub suite_name: String,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+
+    // Whether all the steps passed
+    pub passed: bool,
+
+    pub passed_count: usize,
+    pub failed_count: usize,
+    pub skipped_count: usize,
+
+    // The results of the steps, in the order of the suite
+    pub steps: Vec<StepResult>,
+}
+
+impl SuiteResult {
+    pub fn new(suite_name: &str, started_at: DateTime<Utc>, steps: Vec<StepResult>) -> Self {
+        let count = |status: StepStatus| steps.iter().filter(|step| step.status == status).count();
+        let passed_count = count(StepStatus::Passed);
+
+        Self {
+            suite_name: suite_name.to_string(),
+            started_at,
+            finished_at: Utc::now(),
+            passed: passed_count == steps.len(),
+            passed_count,
+            failed_count: count(StepStatus::Failed) + count(StepStatus::Error),
+            skipped_count: count(StepStatus::Skipped),
+            steps,
+        }
+    }
+}
+
+// Emitted to the frontend every time a step of a running suite finishes
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SuiteStepEvent {
+    pub suite_name: String,
+
+    // The position of the step in the suite
+    pub index: usize,
+
+    pub result: StepResult,
 }
 
 // A Project represents a collection of tests and suites
@@ -95,11 +347,20 @@ pub struct Project {
     // The (optional) description of the content
     // of the Project.
     pub description: Option<String>,
-    // FIXME @oleiade: eventually, we want environment to be also definable
-    // at the project level (and give it precedence) but for the hackathon
-    // we start and stick with an app-wide "global" environment.
-    // environment: Environment,
+    // The environments of the Project are stored next to its configuration,
+    // and managed through LocalProjectManager::environment_manager.
     pub project_config: Option<ProjectConfig>,
+
+    // The number of tests and suites of the Project
+    #[serde(default)]
+    pub test_count: usize,
+
+    #[serde(default)]
+    pub suite_count: usize,
+
+    // When a file of the Project was last changed
+    #[serde(default)]
+    pub last_modified: Option<DateTime<Utc>>,
 }
 
 impl Project {
@@ -109,6 +370,9 @@ impl Project {
             name: name.to_string(),
             description: description.map(|s| s.to_string()),
             project_config: None,
+            test_count: 0,
+            suite_count: 0,
+            last_modified: None,
         }
     }
 
@@ -120,7 +384,15 @@ impl Project {
 // project configuration
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct ProjectConfig {
+    // The cloud token is only ever held in memory: it is persisted
+    // in the secret store, and never written to the configuration file.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub cloud_token: Option<String>,
+
+    // The key the cloud token is stored under in the secret store
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cloud_token_ref: Option<String>,
+
     pub cloud_project_id: Option<String>,
 }
 
@@ -128,21 +400,26 @@ impl ProjectConfig {
     pub fn new() -> Self {
         Self {
             cloud_token: None,
+            cloud_token_ref: None,
             cloud_project_id: None,
         }
     }
 }
 
 // Represents an Environment with its key/value variable pairs
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Environment {
-    name: String,
-    description: String,
-    variables: BTreeMap<String, String>,
+    pub name: String,
+    pub description: String,
+    pub variables: BTreeMap<String, EnvironmentVariable>,
 }
 
 impl Environment {
-    pub fn new(name: &str, description: &str, variables: BTreeMap<String, String>) -> Self {
+    pub fn new(
+        name: &str,
+        description: &str,
+        variables: BTreeMap<String, EnvironmentVariable>,
+    ) -> Self {
         Self {
             name: name.to_string(),
             description: description.to_string(),
@@ -164,11 +441,58 @@ impl Environment {
     // }
 }
 
+// The value a secret variable holds when sent to the frontend, in
+// place of its actual value.
+pub const SECRET_MASK: &str = "********";
+
+// A variable of an environment.
+//
+// The values of secret variables are kept in the secret store: they are
+// never written to the environments file, and are masked when sent to the
+// frontend. They are only materialized when injected into a k6 run.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct EnvironmentVariable {
+    pub value: String,
+    pub secret: bool,
+}
+
+impl EnvironmentVariable {
+    pub fn new(value: &str, secret: bool) -> Self {
+        Self {
+            value: value.to_string(),
+            secret,
+        }
+    }
+
+    // Returns true if the variable holds the masked value of a secret
+    pub fn is_masked(&self) -> bool {
+        self.value == SECRET_MASK
+    }
+}
+
+// The scope an environment is defined in
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum EnvironmentScope {
+    // The app-wide environments
+    Global,
+
+    // The environments of a project, which take precedence over the global ones
+    Project,
+}
+
+// A variable in effect for a project, along with the scope it comes from
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ResolvedVariable {
+    pub value: String,
+    pub secret: bool,
+    pub scope: EnvironmentScope,
+}
+
 // The environment data file, it includes the currently
 // active environment.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct EnvironmentsData {
-    environments: Vec<Environment>,
+    pub environments: Vec<Environment>,
 
     // active environment name
     pub active: String,
@@ -196,3 +520,366 @@ pub struct CloudTestListResponse {
     #[serde(rename(deserialize = "k6-tests"))]
     pub k6_tests: Vec<CloudTest>,
 }
+
+// The credentials used to interact with the cloud, as resolved
+// for a given project.
+#[derive(Clone, Debug)]
+pub struct CloudCredentials {
+    pub token: String,
+    pub project_id: Option<String>,
+}
+
+// A test run started in the cloud from kroco6.
+//
+// It is returned to the frontend as soon as the cloud acknowledges
+// the run, and can be used to correlate the status events that
+// follow.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CloudRun {
+    pub test_run_id: u32,
+    pub url: String,
+}
+
+// The status of a cloud test run, as reported to the frontend
+// while we follow its progress.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum CloudRunStatus {
+    Initializing,
+    Running,
+    Finished,
+
+    // The run went through, but some of its thresholds failed
+    ThresholdsFailed,
+    AbortedByThreshold,
+    AbortedByUser,
+    Failed,
+}
+
+impl CloudRunStatus {
+    // Maps the cloud API's numeric run and result statuses to a CloudRunStatus.
+    //
+    // See the k6 cloud REST API documentation for the meaning of
+    // each of the codes.
+    pub fn from_test_run(test_run: &CloudTestRun) -> Self {
+        match test_run.run_status {
+            // created, validated, queued or initializing
+            -2..=1 => CloudRunStatus::Initializing,
+            2 => CloudRunStatus::Running,
+            // the result status is 1 when thresholds failed
+            3 if test_run.result_status == 1 => CloudRunStatus::ThresholdsFailed,
+            3 => CloudRunStatus::Finished,
+            5 => CloudRunStatus::AbortedByUser,
+            8 => CloudRunStatus::AbortedByThreshold,
+            // timed out, aborted by the system, by a script error or by a limit
+            _ => CloudRunStatus::Failed,
+        }
+    }
+
+    // Maps the exit code of the k6 process attached to a run to the status
+    // the run ended with, for when the cloud API can't tell.
+    pub fn from_exit_code(exit_code: Option<i32>) -> Self {
+        match exit_code {
+            Some(0) => CloudRunStatus::Finished,
+            // k6 exits with 99 when thresholds failed
+            Some(99) => CloudRunStatus::ThresholdsFailed,
+            _ => CloudRunStatus::Failed,
+        }
+    }
+
+    // Returns true if the run will not progress any further.
+    pub fn is_terminal(&self) -> bool {
+        !matches!(self, CloudRunStatus::Initializing | CloudRunStatus::Running)
+    }
+}
+
+// The payload of the `cloud-run-status` event.
+#[derive(Clone, Debug, Serialize)]
+pub struct CloudRunStatusEvent {
+    pub test_run_id: u32,
+    pub status: CloudRunStatus,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CloudTestRun {
+    pub id: u32,
+    pub run_status: i32,
+    pub result_status: i32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CloudTestRunResponse {
+    #[serde(rename(deserialize = "k6-run"))]
+    pub k6_run: CloudTestRun,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CloudProject {
+    pub id: u32,
+    pub name: String,
+}
+
+// The outcome of checking a cloud token and project id against the cloud API.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct CloudConfigValidation {
+    // Whether the cloud API accepted the token
+    pub token_valid: bool,
+
+    // Whether the project exists
+    pub project_exists: bool,
+
+    // Whether the account the token belongs to has access to the project
+    pub project_accessible: bool,
+
+    // The name of the project, when it is accessible
+    pub project_name: Option<String>,
+
+    // A message explaining the first problem found, meant to be
+    // displayed as-is by the UI.
+    pub error: Option<String>,
+}
+
+// The content the recorder's mitmproxy addon reports in place of binary
+// bodies (images, fonts, media...), which it doesn't capture.
+pub const REDACTED_CONTENT: &str = "<redacted content>";
+
+// A single HTTP exchange captured by the recorder.
+//
+// It mirrors the JSON representation of a flow emitted by the
+// recorder's mitmproxy addon, limited to the fields we make use of.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RecordedFlow {
+    pub id: String,
+
+    // Unix timestamp, in seconds, of the creation of the flow
+    pub timestamp_created: f64,
+
+    pub request: RecordedRequest,
+
+    // The response, absent until the server answered
+    #[serde(default)]
+    pub response: Option<RecordedResponse>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RecordedRequest {
+    pub method: String,
+    pub scheme: String,
+    pub host: String,
+    pub port: u16,
+
+    // The path of the request, including its query string
+    pub path: String,
+    pub http_version: String,
+    pub headers: Vec<(String, String)>,
+
+    #[serde(default)]
+    pub content: String,
+
+    // The size in bytes of the body as it was sent, if known
+    #[serde(default, rename = "contentLength")]
+    pub content_length: Option<u64>,
+
+    pub timestamp_start: f64,
+    pub timestamp_end: Option<f64>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RecordedResponse {
+    pub http_version: String,
+    pub status_code: u16,
+    pub reason: String,
+    pub headers: Vec<(String, String)>,
+
+    #[serde(default)]
+    pub content: String,
+
+    // The size in bytes of the body as it was received, if known
+    #[serde(default, rename = "contentLength")]
+    pub content_length: Option<u64>,
+
+    pub timestamp_start: Option<f64>,
+    pub timestamp_end: Option<f64>,
+}
+
+impl RecordedRequest {
+    // Returns the full URL of the request
+    pub fn url(&self) -> String {
+        let default_port = matches!(
+            (self.scheme.as_str(), self.port),
+            ("http", 80) | ("https", 443)
+        );
+
+        if default_port {
+            format!("{}://{}{}", self.scheme, self.host, self.path)
+        } else {
+            format!("{}://{}:{}{}", self.scheme, self.host, self.port, self.path)
+        }
+    }
+
+    // Returns true if the body of the request wasn't captured by the recorder
+    pub fn is_content_redacted(&self) -> bool {
+        self.content == REDACTED_CONTENT
+    }
+}
+
+impl RecordedResponse {
+    // Returns true if the body of the response wasn't captured by the recorder
+    pub fn is_content_redacted(&self) -> bool {
+        self.content == REDACTED_CONTENT
+    }
+}
+
+// The filters applied to the flows seen by the recorder, deciding
+// which ones are recorded and reported to the frontend.
+//
+// Host patterns are globs in which `*` matches any sequence of
+// characters, e.g. `*.example.com`.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct RecordingFilter {
+    // The hosts to record, all of them are recorded when empty
+    #[serde(default)]
+    pub allow_hosts: Vec<String>,
+
+    // The hosts not to record, taking precedence over allow_hosts
+    #[serde(default)]
+    pub deny_hosts: Vec<String>,
+
+    // The prefixes of the response content types not to record,
+    // e.g. `image/` or `font/woff2`
+    #[serde(default)]
+    pub deny_content_types: Vec<String>,
+
+    // The methods to record, all of them are recorded when empty
+    #[serde(default)]
+    pub methods: Vec<String>,
+}
+
+// A Chromium-family browser found on the machine, which the recorder can use
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DetectedBrowser {
+    pub name: String,
+    pub path: PathBuf,
+}
+
+// A named boundary added while recording, such as "login" or "checkout",
+// which starts a new group in the generated script.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RecordingMarker {
+    pub label: String,
+
+    // Unix timestamp in seconds at which the marker was added
+    pub timestamp: f64,
+}
+
+// A recorder session, as saved in its project
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RecordingSession {
+    pub id: String,
+    pub started_at: DateTime<Utc>,
+    pub flows: Vec<RecordedFlow>,
+
+    #[serde(default)]
+    pub markers: Vec<RecordingMarker>,
+}
+
+// A summary of a saved recorder session, as listed to the frontend
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RecordingSummary {
+    pub id: String,
+    pub started_at: DateTime<Utc>,
+    pub flow_count: usize,
+}
+
+impl From<&RecordingSession> for RecordingSummary {
+    fn from(session: &RecordingSession) -> Self {
+        Self {
+            id: session.id.clone(),
+            started_at: session.started_at,
+            flow_count: session.flows.len(),
+        }
+    }
+}
+
+// The options of the generation of a k6 script out of recorded flows
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ScriptOptions {
+    // Whether to leave requests fetching static assets, such as
+    // stylesheets, scripts, images or fonts, out of the script
+    #[serde(default = "default_true")]
+    pub filter_static_assets: bool,
+
+    // Whether to replay the pauses made between pages as think time
+    #[serde(default = "default_true")]
+    pub think_time: bool,
+}
+
+impl Default for ScriptOptions {
+    fn default() -> Self {
+        Self {
+            filter_static_assets: true,
+            think_time: true,
+        }
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+// The result of importing an HTTP Archive (HAR) file
+#[derive(Clone, Debug, Serialize)]
+pub struct ImportedHar {
+    // The id of the recorder session the archive was saved as
+    pub recording_id: String,
+
+    // The flows of the archive, as if they had been recorded
+    pub flows: Vec<RecordedFlow>,
+
+    // The test created from the flows, if one was requested
+    pub test: Option<Test>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn metadata_updates_normalize_tags_and_owners() {
+        let mut metadata = TestMetadata::new(Utc::now());
+        metadata.apply(TestMetadataUpdate {
+            description: Some("Checks the checkout".to_string()),
+            tags: Some(
+                ["nightly ", " smoke", "", "smoke", "  "]
+                    .map(String::from)
+                    .to_vec(),
+            ),
+            owner: Some("  payments team ".to_string()),
+        });
+
+        assert_eq!(metadata.description, "Checks the checkout");
+        assert_eq!(metadata.tags, vec!["nightly", "smoke"]);
+        assert_eq!(metadata.owner.as_deref(), Some("payments team"));
+
+        // unset fields are left as is, and an empty owner clears it
+        metadata.apply(TestMetadataUpdate {
+            owner: Some(" ".to_string()),
+            ..TestMetadataUpdate::default()
+        });
+        assert_eq!(metadata.description, "Checks the checkout");
+        assert_eq!(metadata.tags, vec!["nightly", "smoke"]);
+        assert_eq!(metadata.owner, None);
+    }
+
+    #[test]
+    fn tags_are_matched_regardless_of_case() {
+        let mut metadata = TestMetadata::new(Utc::now());
+        metadata.apply(TestMetadataUpdate {
+            tags: Some(vec!["Smoke".to_string()]),
+            ..TestMetadataUpdate::default()
+        });
+
+        assert!(metadata.has_tag("smoke"));
+        assert!(metadata.has_tag(" SMOKE "));
+        assert!(!metadata.has_tag("smok"));
+    }
+}
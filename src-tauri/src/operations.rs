@@ -1,14 +1,35 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::sync::{Arc, Mutex};
 use std::{fs, io};
 
-use crate::models::{Environment, EnvironmentsData, Project, ProjectConfig, Test, TestKind};
+use chrono::{DateTime, Utc};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+
+use crate::interpolation;
+use crate::models::{
+    Environment, EnvironmentScope, EnvironmentVariable, EnvironmentsData, Project, ProjectConfig,
+    RecordingSession, RecordingSummary, ResolvedVariable, StepStatus, Suite, Test, TestCollection,
+    TestKind, TestMetadata, TestMetadataUpdate, TestSummary, SECRET_MASK,
+};
+use crate::secrets::SecretStore;
+use crate::storage;
 
 const PROJECTS_DIR: &str = "projects";
 const DEFAULT_PROJECT_NAME: &str = "default";
+const PROJECT_FILE: &str = "project.json";
 const PROJECT_CONFIG_FILE: &str = "project_config.json";
 const ENVIRONMENT_FILE: &str = "environments.json";
+const RECORDINGS_DIR: &str = "recordings";
+const SUITES_DIR: &str = "suites";
+const METADATA_DIR: &str = "metadata";
+const BACKUPS_DIR: &str = "backups";
+
+// The field holding the version of the schema of stored JSON files
+const SCHEMA_VERSION_FIELD: &str = "schema_version";
 
 pub trait ProjectManager {
     // Initialize the project manager, ensuring that the underlying
@@ -17,13 +38,16 @@ pub trait ProjectManager {
 
     // List all projects.
     //
-    // Returns the list of projects that are currently stored
-    // in the underlying storage.
+    // Returns the list of projects that are currently stored in the
+    // underlying storage, with their description and the number of their
+    // tests and suites, but without their configuration nor the summaries
+    // of their tests and suites, which get_project returns.
     fn list_projects(&self) -> io::Result<Vec<Project>>;
 
     // Get a project by name.
     //
-    // Returns the project with the given name, if it exists.
+    // Returns the project with the given name, if it exists, along with
+    // its configuration and the summaries of its tests and suites.
     // If it doesn't, returns an error of kind NotFound.
     fn get_project(&self, name: &str) -> io::Result<Project>;
 
@@ -35,8 +59,10 @@ pub trait ProjectManager {
     // List all tests in a project.
     //
     // Returns the list of tests that are currently stored
-    // in the underlying storage for the given project.
-    fn list_tests(&self, project_name: &str) -> io::Result<Vec<Test>>;
+    // in the underlying storage for the given project, along with their
+    // metadata. When a tag is given, only the tests labelled with it are
+    // returned.
+    fn list_tests(&self, project_name: &str, tag: Option<&str>) -> io::Result<Vec<Test>>;
 
     // Get a test by name.
     //
@@ -66,15 +92,92 @@ pub trait ProjectManager {
         project_name: &str,
         project_config: ProjectConfig,
     ) -> io::Result<()>;
+
+    // Saves a recorder session in a project.
+    //
+    // Saving a session that was already saved replaces it.
+    fn save_recording(&self, project_name: &str, session: &RecordingSession) -> io::Result<()>;
+
+    // List the recorder sessions saved in a project.
+    //
+    // Returns the summaries of the sessions, most recent first.
+    fn list_recordings(&self, project_name: &str) -> io::Result<Vec<RecordingSummary>>;
+
+    // Load a recorder session saved in a project.
+    //
+    // If it doesn't exist, returns an error of kind NotFound.
+    fn load_recording(
+        &self,
+        project_name: &str,
+        recording_id: &str,
+    ) -> io::Result<RecordingSession>;
+
+    // Delete a recorder session saved in a project.
+    fn delete_recording(&self, project_name: &str, recording_id: &str) -> io::Result<()>;
+
+    // List the suites of a project, sorted by name.
+    fn list_suites(&self, project_name: &str) -> io::Result<Vec<Suite>>;
+
+    // Get a suite by name.
+    //
+    // If it doesn't exist, returns an error of kind NotFound.
+    fn get_suite(&self, project_name: &str, suite_name: &str) -> io::Result<Suite>;
+
+    // Saves a suite in a project.
+    //
+    // Saving a suite with the name of an existing one replaces it. All
+    // its steps must reference tests of the project.
+    fn save_suite(&self, project_name: &str, suite: &Suite) -> io::Result<()>;
+
+    // Delete a suite of a project.
+    fn delete_suite(&self, project_name: &str, suite_name: &str) -> io::Result<()>;
+
+    // Get the metadata of a test.
+    //
+    // Tests created before metadata was introduced have default metadata,
+    // dated from the last change of their file.
+    fn get_test_metadata(&self, project_name: &str, test_name: &str) -> io::Result<TestMetadata>;
+
+    // Updates the description, tags or owner of a test.
+    //
+    // Returns the metadata of the test after the update.
+    fn update_test_metadata(
+        &self,
+        project_name: &str,
+        test_name: &str,
+        update: TestMetadataUpdate,
+    ) -> io::Result<TestMetadata>;
+
+    // Records the status of the last run of a test.
+    fn record_test_run(
+        &self,
+        project_name: &str,
+        test_name: &str,
+        status: StepStatus,
+    ) -> io::Result<()>;
 }
 
 pub struct LocalProjectManager {
     base_path: PathBuf,
+
+    // The store holding the projects' secrets, such as cloud tokens
+    secret_store: Arc<dyn SecretStore>,
+
+    // Serializes the changes to the environments of the projects
+    environments_lock: Arc<Mutex<()>>,
+
+    // Serializes the read-modify-write cycles on the metadata of tests
+    metadata_lock: Mutex<()>,
 }
 
 impl LocalProjectManager {
-    pub fn new(base_path: PathBuf) -> Self {
-        Self { base_path }
+    pub fn new(base_path: PathBuf, secret_store: Arc<dyn SecretStore>) -> Self {
+        Self {
+            base_path,
+            secret_store,
+            environments_lock: Arc::new(Mutex::new(())),
+            metadata_lock: Mutex::new(()),
+        }
     }
 
     // Returns the path of the projects directory
@@ -85,6 +188,182 @@ impl LocalProjectManager {
     fn project_path(&self, name: &str) -> PathBuf {
         self.projects_dir().join(name)
     }
+
+    // Returns the manager of the environments defined by the given project.
+    pub fn environment_manager(&self, project_name: &str) -> io::Result<EnvironmentManager> {
+        let project_path = self.project_path(project_name);
+        if !project_path.exists() {
+            return Err(io::Error::new(io::ErrorKind::NotFound, "project not found"));
+        }
+
+        Ok(EnvironmentManager::for_project(
+            &project_path,
+            project_name,
+            self.secret_store.clone(),
+            self.environments_lock.clone(),
+        ))
+    }
+
+    // Returns the path of the file a project's recorder session is saved to
+    fn recording_path(&self, project_name: &str, recording_id: &str) -> io::Result<PathBuf> {
+        // ids end up in file names, make sure they can't escape the recordings directory
+        let valid_id = !recording_id.is_empty()
+            && recording_id
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !valid_id {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid recording id",
+            ));
+        }
+
+        Ok(self
+            .project_path(project_name)
+            .join(RECORDINGS_DIR)
+            .join(recording_id.to_string() + ".json"))
+    }
+
+    // Returns the path of the file the metadata of a project's test is saved to
+    fn test_metadata_path(&self, project_name: &str, test_name: &str) -> PathBuf {
+        self.project_path(project_name)
+            .join(METADATA_DIR)
+            .join(test_name.to_string() + ".json")
+    }
+
+    // Reads the metadata of the test stored at the given path
+    fn read_test_metadata(
+        &self,
+        project_name: &str,
+        test_name: &str,
+        test_path: &Path,
+    ) -> io::Result<TestMetadata> {
+        let metadata_path = self.test_metadata_path(project_name, test_name);
+        if !metadata_path.exists() {
+            let created_at = modified_at(test_path).unwrap_or_else(Utc::now);
+            return Ok(TestMetadata::new(created_at));
+        }
+
+        read_versioned(&metadata_path, &TEST_METADATA_SCHEMA)
+    }
+
+    fn write_test_metadata(
+        &self,
+        project_name: &str,
+        test_name: &str,
+        metadata: &TestMetadata,
+    ) -> io::Result<()> {
+        let metadata_dir = self.project_path(project_name).join(METADATA_DIR);
+        if !metadata_dir.exists() {
+            fs::create_dir(&metadata_dir)?;
+        }
+
+        let metadata_path = self.test_metadata_path(project_name, test_name);
+        write_versioned(&metadata_path, metadata, &TEST_METADATA_SCHEMA)
+    }
+
+    // Applies a change to the metadata of a test, and returns the changed metadata
+    fn change_test_metadata(
+        &self,
+        project_name: &str,
+        test_name: &str,
+        change: impl FnOnce(&mut TestMetadata),
+    ) -> io::Result<TestMetadata> {
+        let project_path = self.project_path(project_name);
+        if !project_path.exists() {
+            return Err(io::Error::new(io::ErrorKind::NotFound, "project not found"));
+        }
+
+        let test_path = get_file_with_basename(&project_path, test_name)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "test not found"))?;
+
+        let _guard = self.metadata_lock.lock().unwrap();
+
+        let mut metadata = self.read_test_metadata(project_name, test_name, &test_path)?;
+        change(&mut metadata);
+        self.write_test_metadata(project_name, test_name, &metadata)?;
+
+        Ok(metadata)
+    }
+
+    // Returns the path of the file a project's suite is saved to
+    fn suite_path(&self, project_name: &str, suite_name: &str) -> io::Result<PathBuf> {
+        // names end up in file names, make sure they can't escape the suites directory
+        let valid_name = !suite_name.trim().is_empty()
+            && !suite_name.starts_with('.')
+            && !suite_name.contains(['/', '\\']);
+        if !valid_name {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid suite name",
+            ));
+        }
+
+        Ok(self
+            .project_path(project_name)
+            .join(SUITES_DIR)
+            .join(suite_name.to_string() + ".json"))
+    }
+
+    // Returns the names of the stored projects
+    fn project_names(&self) -> io::Result<Vec<String>> {
+        let mut project_names = vec![];
+        for entry in fs::read_dir(self.projects_dir())? {
+            let path = entry?.path();
+            if path.is_dir() {
+                let project_name = path
+                    .file_name()
+                    .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "missing file name"))?
+                    .to_str()
+                    .ok_or_else(|| {
+                        io::Error::new(io::ErrorKind::InvalidData, "invalid UTF-8 in file name")
+                    })?;
+                project_names.push(project_name.to_string());
+            }
+        }
+
+        Ok(project_names)
+    }
+
+    // Returns the project with the given name as it is listed: with its
+    // description, and its tests and suites counted rather than read.
+    fn project_summary(&self, name: &str) -> io::Result<Project> {
+        let project_path = self.project_path(name);
+
+        let project_file = read_project_file(&project_path);
+        let mut project = Project::new(name, project_file.description.as_deref());
+
+        let mut modified_paths: Vec<PathBuf> = test_files(&project_path)?
+            .into_iter()
+            .map(|test_file| test_file.path)
+            .collect();
+        project.test_count = modified_paths.len();
+
+        let suite_paths = suite_files(&project_path)?;
+        project.suite_count = suite_paths.len();
+        modified_paths.extend(suite_paths);
+
+        project.last_modified = last_modified(&project_path, &modified_paths);
+
+        Ok(project)
+    }
+
+    // Moves the cloud token of a project configuration saved by an earlier
+    // version of the app, which held it in plaintext, to the secret store.
+    fn migrate_cloud_token(&self, project_name: &str) -> io::Result<()> {
+        let project_config_path = self.project_path(project_name).join(PROJECT_CONFIG_FILE);
+        if !project_config_path.exists() {
+            return Ok(());
+        }
+
+        let project_config: ProjectConfig =
+            read_versioned(&project_config_path, &PROJECT_CONFIG_SCHEMA)?;
+        if project_config.cloud_token.is_some() {
+            self.save_project_config(project_name, project_config)?;
+        }
+
+        Ok(())
+    }
 }
 
 impl ProjectManager for LocalProjectManager {
@@ -100,6 +379,17 @@ impl ProjectManager for LocalProjectManager {
             self.create_project(Project::default())?;
         }
 
+        // Configurations saved by earlier versions hold the cloud token
+        // in plaintext, saving them again moves it to the secret store.
+        for project_name in self.project_names()? {
+            if let Err(e) = self.migrate_cloud_token(&project_name) {
+                eprintln!(
+                    "failed to move the cloud token of {} to the secret store: {}",
+                    project_name, e
+                );
+            }
+        }
+
         Ok(())
     }
 
@@ -114,18 +404,11 @@ impl ProjectManager for LocalProjectManager {
         }
 
         let mut projects = vec![];
-        for entry in fs::read_dir(projects_dir)? {
-            let entry = entry?;
-            let path = entry.path();
-            if path.is_dir() {
-                let file_name = path
-                    .file_name()
-                    .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "missing file name"))?
-                    .to_str()
-                    .ok_or_else(|| {
-                        io::Error::new(io::ErrorKind::InvalidData, "invalid UTF-8 in file name")
-                    })?;
-                projects.push(Project::new(file_name, None));
+        for project_name in self.project_names()? {
+            // a project which can't be read doesn't keep the others from being listed
+            match self.project_summary(&project_name) {
+                Ok(project) => projects.push(project),
+                Err(e) => eprintln!("failed to list project {}: {}", project_name, e),
             }
         }
 
@@ -139,7 +422,47 @@ impl ProjectManager for LocalProjectManager {
             return Err(io::Error::new(io::ErrorKind::NotFound, "project not found"));
         }
 
-        Ok(Project::new(name, None))
+        let project_file = read_project_file(&project_path);
+        let mut project = Project::new(name, project_file.description.as_deref());
+        project.project_config = Some(self.load_project_config(name)?);
+
+        // files which can't be read are reported, and left out of the project
+        let mut modified_paths = vec![];
+        for test_file in test_files(&project_path)? {
+            let last_modified = modified_at(&test_file.path);
+            let tags = match self.read_test_metadata(name, &test_file.name, &test_file.path) {
+                Ok(metadata) => metadata.tags,
+                Err(e) => {
+                    eprintln!("failed to read the metadata of {}: {}", test_file.name, e);
+                    vec![]
+                }
+            };
+            project
+                .test_collections
+                .push(TestCollection::Test(TestSummary {
+                    name: test_file.name,
+                    kind: test_file.kind,
+                    last_modified,
+                    tags,
+                }));
+            project.test_count += 1;
+            modified_paths.push(test_file.path);
+        }
+
+        for suite_path in suite_files(&project_path)? {
+            match read_versioned(&suite_path, &SUITE_SCHEMA) {
+                Ok(suite) => {
+                    project.test_collections.push(TestCollection::Suite(suite));
+                    project.suite_count += 1;
+                }
+                Err(e) => eprintln!("failed to read suite {}: {}", suite_path.display(), e),
+            }
+            modified_paths.push(suite_path);
+        }
+
+        project.last_modified = last_modified(&project_path, &modified_paths);
+
+        Ok(project)
     }
 
     // Create a new local project
@@ -157,7 +480,16 @@ impl ProjectManager for LocalProjectManager {
         let project_path = projects_dir.join(&project.name);
 
         // Create the underlying directory for the project
-        fs::create_dir(project_path)?;
+        fs::create_dir(&project_path)?;
+
+        let project_file = ProjectFile {
+            description: project.description.clone(),
+        };
+        write_versioned(
+            &project_path.join(PROJECT_FILE),
+            &project_file,
+            &PROJECT_SCHEMA,
+        )?;
 
         Ok(project.clone())
     }
@@ -172,10 +504,24 @@ impl ProjectManager for LocalProjectManager {
             ));
         }
 
+        // Projects created before they had a configuration don't have
+        // a configuration file, they have the default configuration.
         let project_config_path = project_path.join(PROJECT_CONFIG_FILE);
+        if !project_config_path.exists() {
+            return Ok(ProjectConfig::new());
+        }
+
+        let mut project_config: ProjectConfig =
+            read_versioned(&project_config_path, &PROJECT_CONFIG_SCHEMA)?;
+
+        // a cloud token still held in plaintext is moved to the secret
+        // store when the app starts, see initialize
+        if project_config.cloud_token.is_none() {
+            if let Some(cloud_token_ref) = &project_config.cloud_token_ref {
+                project_config.cloud_token = self.secret_store.get(cloud_token_ref)?;
+            }
+        }
 
-        let file = fs::File::open(&project_config_path)?;
-        let project_config = serde_json::from_reader(file)?;
         Ok(project_config)
     }
 
@@ -183,7 +529,7 @@ impl ProjectManager for LocalProjectManager {
     fn save_project_config(
         &self,
         project_name: &str,
-        project_config: ProjectConfig,
+        mut project_config: ProjectConfig,
     ) -> io::Result<()> {
         let project_path = self.projects_dir().join(project_name);
         if !project_path.exists() {
@@ -193,68 +539,47 @@ impl ProjectManager for LocalProjectManager {
             ));
         }
 
-        let project_config_path = project_path.join(PROJECT_CONFIG_FILE);
+        // The cloud token goes to the secret store, and the configuration
+        // file only holds a reference to it.
+        let cloud_token_key = project_cloud_token_key(project_name);
+        match project_config.cloud_token.take() {
+            Some(cloud_token) if !cloud_token.is_empty() => {
+                self.secret_store.set(&cloud_token_key, &cloud_token)?;
+                project_config.cloud_token_ref = Some(cloud_token_key);
+            }
+            _ => {
+                self.secret_store.delete(&cloud_token_key)?;
+                project_config.cloud_token_ref = None;
+            }
+        }
 
-        let file = fs::File::create(&project_config_path)?;
-        serde_json::to_writer_pretty(file, &project_config)?;
+        let project_config_path = project_path.join(PROJECT_CONFIG_FILE);
 
-        Ok(())
+        write_versioned(
+            &project_config_path,
+            &project_config,
+            &PROJECT_CONFIG_SCHEMA,
+        )
     }
 
-    fn list_tests(&self, project_name: &str) -> io::Result<Vec<Test>> {
+    fn list_tests(&self, project_name: &str, tag: Option<&str>) -> io::Result<Vec<Test>> {
         let project_path = self.projects_dir().join(project_name);
         if !project_path.exists() {
             return Err(io::Error::new(io::ErrorKind::NotFound, "project not found"));
         }
 
         let mut tests = vec![];
-        for entry in fs::read_dir(project_path)? {
-            let entry = entry?;
-            let path = entry.path();
-            if path.is_file() {
-                let name = path
-                    .file_stem()
-                    .ok_or(io::Error::new(
-                        io::ErrorKind::InvalidData,
-                        "invalid file name",
-                    ))?
-                    .to_str()
-                    .ok_or(io::Error::new(
-                        io::ErrorKind::InvalidData,
-                        "invalid file name",
-                    ))?
-                    .to_string();
-
-                let kind = path
-                    .extension()
-                    .ok_or(io::Error::new(
-                        io::ErrorKind::InvalidData,
-                        "invalid file extension",
-                    ))?
-                    .to_str()
-                    .ok_or(io::Error::new(
-                        io::ErrorKind::InvalidData,
-                        "invalid file extension",
-                    ))?;
-
-                // skip json as it's not a test but configuration
-                if kind == "json" {
-                    continue;
-                }
-
-                // Read the content of the file
-                let content = fs::read_to_string(&path)?;
-
-                let test = Test::new(
-                    &name,
-                    TestKind::from_str(kind).map_err(|_| {
-                        io::Error::new(io::ErrorKind::InvalidData, "invalid test kind")
-                    })?,
-                    &content,
-                );
-
-                tests.push(test);
+        for test_file in test_files(&project_path)? {
+            let metadata =
+                self.read_test_metadata(project_name, &test_file.name, &test_file.path)?;
+            if tag.is_some_and(|tag| !metadata.has_tag(tag)) {
+                continue;
             }
+
+            let content = fs::read_to_string(&test_file.path)?;
+            let mut test = Test::new(&test_file.name, test_file.kind, &content);
+            test.metadata = Some(metadata);
+            tests.push(test);
         }
 
         Ok(tests)
@@ -282,13 +607,15 @@ impl ProjectManager for LocalProjectManager {
 
                 let content = fs::read_to_string(&test_path)?;
 
-                let test = Test::new(
+                let mut test = Test::new(
                     test_name,
                     TestKind::from_str(kind).map_err(|_| {
                         io::Error::new(io::ErrorKind::InvalidData, "invalid test kind")
                     })?,
                     &content,
                 );
+                test.metadata =
+                    Some(self.read_test_metadata(project_name, test_name, &test_path)?);
 
                 Ok(test)
             }
@@ -316,7 +643,14 @@ impl ProjectManager for LocalProjectManager {
                         "invalid file extension",
                     ))?;
 
-                fs::remove_file(test_path)
+                fs::remove_file(test_path)?;
+
+                let metadata_path = self.test_metadata_path(project_name, test_name);
+                if metadata_path.exists() {
+                    fs::remove_file(metadata_path)?;
+                }
+
+                Ok(())
             }
             None => Err(io::Error::new(io::ErrorKind::NotFound, "test not found")),
         }
@@ -345,9 +679,23 @@ impl ProjectManager for LocalProjectManager {
             ));
         }
 
-        fs::write(&test_path, &test.content)?;
+        storage::write_atomic(&test_path, &test.content)?;
+
+        // the metadata provided along with the test only sets its description,
+        // tags and owner, the rest is maintained by the project manager
+        let mut metadata = TestMetadata::new(Utc::now());
+        if let Some(provided) = test.metadata {
+            metadata.apply(TestMetadataUpdate {
+                description: Some(provided.description),
+                tags: Some(provided.tags),
+                owner: provided.owner,
+            });
+        }
+        self.write_test_metadata(project_name, &test.name, &metadata)?;
 
-        Ok(Test::new(&test.name, test.kind, &test.content))
+        let mut created = Test::new(&test.name, test.kind, &test.content);
+        created.metadata = Some(metadata);
+        Ok(created)
     }
 
     fn save_test(&self, project_name: &str, test_name: &str, new_content: &str) -> io::Result<()> {
@@ -359,69 +707,1971 @@ impl ProjectManager for LocalProjectManager {
         let test_path = get_file_with_basename(&project_path, test_name);
         match test_path {
             Some(path) => {
-                fs::write(path, new_content)?;
+                storage::write_atomic(&path, new_content)?;
+                self.change_test_metadata(project_name, test_name, |metadata| {
+                    metadata.updated_at = Utc::now();
+                })?;
                 Ok(())
             }
             None => Err(io::Error::new(io::ErrorKind::NotFound, "test not found")),
         }
     }
-}
 
-pub struct EnvironmentManager {
-    file_path: PathBuf,
-}
+    fn save_recording(&self, project_name: &str, session: &RecordingSession) -> io::Result<()> {
+        let project_path = self.project_path(project_name);
+        if !project_path.exists() {
+            return Err(io::Error::new(io::ErrorKind::NotFound, "project not found"));
+        }
 
-impl EnvironmentManager {
-    pub fn new(storage_path: PathBuf) -> Self {
-        let file_path = Path::new(&storage_path).join(ENVIRONMENT_FILE);
-        Self { file_path }
+        let recordings_dir = project_path.join(RECORDINGS_DIR);
+        if !recordings_dir.exists() {
+            fs::create_dir(&recordings_dir)?;
+        }
+
+        let recording_path = self.recording_path(project_name, &session.id)?;
+        write_versioned(&recording_path, session, &RECORDING_SCHEMA)
     }
 
-    pub fn initialize(&self) -> io::Result<()> {
-        // if the file doesn't exist create the default environment
-        if !self.file_path.exists() {
-            let file = fs::File::create(&self.file_path)?;
+    fn list_recordings(&self, project_name: &str) -> io::Result<Vec<RecordingSummary>> {
+        let project_path = self.project_path(project_name);
+        if !project_path.exists() {
+            return Err(io::Error::new(io::ErrorKind::NotFound, "project not found"));
+        }
 
-            let default_name = "default";
-            let default_environment =
-                Environment::new(default_name, "default environment", BTreeMap::new());
+        let recordings_dir = project_path.join(RECORDINGS_DIR);
+        if !recordings_dir.exists() {
+            return Ok(vec![]);
+        }
 
-            let environment_data = EnvironmentsData::new(vec![default_environment], default_name);
+        let mut recordings = vec![];
+        for entry in fs::read_dir(recordings_dir)? {
+            let path = entry?.path();
+            if !path.is_file() || path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+                continue;
+            }
 
-            serde_json::to_writer_pretty(file, &environment_data)?;
+            let session: RecordingSession = read_versioned(&path, &RECORDING_SCHEMA)?;
+            recordings.push(RecordingSummary::from(&session));
         }
 
-        Ok(())
+        recordings.sort_by_key(|recording| std::cmp::Reverse(recording.started_at));
+
+        Ok(recordings)
     }
 
-    pub fn load(&self) -> io::Result<EnvironmentsData> {
-        let file = fs::File::open(&self.file_path)?;
-        let environments_data = serde_json::from_reader(file)?;
-        Ok(environments_data)
+    fn load_recording(
+        &self,
+        project_name: &str,
+        recording_id: &str,
+    ) -> io::Result<RecordingSession> {
+        let recording_path = self.recording_path(project_name, recording_id)?;
+        if !recording_path.exists() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "recording not found",
+            ));
+        }
+
+        read_versioned(&recording_path, &RECORDING_SCHEMA)
     }
 
-    pub fn save(&self, environments_data: &EnvironmentsData) -> io::Result<()> {
-        let file = fs::File::create(&self.file_path)?;
-        serde_json::to_writer_pretty(file, environments_data)?;
+    fn delete_recording(&self, project_name: &str, recording_id: &str) -> io::Result<()> {
+        let recording_path = self.recording_path(project_name, recording_id)?;
+        if !recording_path.exists() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "recording not found",
+            ));
+        }
 
-        Ok(())
+        fs::remove_file(recording_path)
     }
-}
 
-// Returns the path of the file with the given basename in the given directory, if it exists.
-//
-// This function allows to check if a test file with the provided `basename` exists in the
-// project directory, regardless of its extension.
-fn get_file_with_basename(directory: &Path, basename: &str) -> Option<PathBuf> {
-    if directory.is_dir() {
-        if let Ok(entries) = fs::read_dir(directory) {
-            for entry in entries.flatten() {
-                let path = entry.path();
-                if path.is_file() && path.file_stem().map_or(false, |s| s == basename) {
-                    return Some(path);
-                }
+    fn list_suites(&self, project_name: &str) -> io::Result<Vec<Suite>> {
+        let project_path = self.project_path(project_name);
+        if !project_path.exists() {
+            return Err(io::Error::new(io::ErrorKind::NotFound, "project not found"));
+        }
+
+        let mut suites = vec![];
+        for path in suite_files(&project_path)? {
+            suites.push(read_versioned(&path, &SUITE_SCHEMA)?);
+        }
+
+        suites.sort_by(|a: &Suite, b: &Suite| a.name.cmp(&b.name));
+
+        Ok(suites)
+    }
+
+    fn get_suite(&self, project_name: &str, suite_name: &str) -> io::Result<Suite> {
+        let suite_path = self.suite_path(project_name, suite_name)?;
+        if !suite_path.exists() {
+            return Err(io::Error::new(io::ErrorKind::NotFound, "suite not found"));
+        }
+
+        read_versioned(&suite_path, &SUITE_SCHEMA)
+    }
+
+    fn save_suite(&self, project_name: &str, suite: &Suite) -> io::Result<()> {
+        let project_path = self.project_path(project_name);
+        if !project_path.exists() {
+            return Err(io::Error::new(io::ErrorKind::NotFound, "project not found"));
+        }
+
+        for step in &suite.steps {
+            if get_file_with_basename(&project_path, &step.test_name).is_none() {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("test {} not found", step.test_name),
+                ));
             }
         }
+
+        let suites_dir = project_path.join(SUITES_DIR);
+        if !suites_dir.exists() {
+            fs::create_dir(&suites_dir)?;
+        }
+
+        let suite_path = self.suite_path(project_name, &suite.name)?;
+        write_versioned(&suite_path, suite, &SUITE_SCHEMA)
+    }
+
+    fn delete_suite(&self, project_name: &str, suite_name: &str) -> io::Result<()> {
+        let suite_path = self.suite_path(project_name, suite_name)?;
+        if !suite_path.exists() {
+            return Err(io::Error::new(io::ErrorKind::NotFound, "suite not found"));
+        }
+
+        fs::remove_file(suite_path)
+    }
+
+    fn get_test_metadata(&self, project_name: &str, test_name: &str) -> io::Result<TestMetadata> {
+        let project_path = self.project_path(project_name);
+        if !project_path.exists() {
+            return Err(io::Error::new(io::ErrorKind::NotFound, "project not found"));
+        }
+
+        let test_path = get_file_with_basename(&project_path, test_name)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "test not found"))?;
+
+        self.read_test_metadata(project_name, test_name, &test_path)
+    }
+
+    fn update_test_metadata(
+        &self,
+        project_name: &str,
+        test_name: &str,
+        update: TestMetadataUpdate,
+    ) -> io::Result<TestMetadata> {
+        self.change_test_metadata(project_name, test_name, |metadata| {
+            metadata.apply(update);
+            metadata.updated_at = Utc::now();
+        })
+    }
+
+    fn record_test_run(
+        &self,
+        project_name: &str,
+        test_name: &str,
+        status: StepStatus,
+    ) -> io::Result<()> {
+        self.change_test_metadata(project_name, test_name, |metadata| {
+            metadata.last_run_status = Some(status);
+            metadata.last_run_at = Some(Utc::now());
+        })?;
+
+        Ok(())
+    }
+}
+
+// The changes an update of the environments makes to the secret store,
+// which are only applied once the environments file is written.
+#[derive(Default)]
+struct SecretChanges {
+    // The new value of each changed secret, None for deleted ones
+    values: BTreeMap<String, Option<String>>,
+}
+
+impl SecretChanges {
+    fn set(&mut self, key: &str, value: &str) {
+        self.values.insert(key.to_string(), Some(value.to_string()));
+    }
+
+    fn delete(&mut self, key: &str) {
+        self.values.insert(key.to_string(), None);
+    }
+}
+
+// EnvironmentManager manages a set of environments: either the app-wide
+// "global" ones, or the ones of a project.
+//
+// All the changes to the environments go through a lock, so that
+// concurrent edits don't overwrite each other.
+pub struct EnvironmentManager {
+    file_path: PathBuf,
+
+    // The scope of the managed environments
+    scope: EnvironmentScope,
+
+    // The store holding the values of secret variables
+    secret_store: Arc<dyn SecretStore>,
+
+    // The prefix of the keys the values of secret variables are stored
+    // under, which keeps the secrets of different scopes apart.
+    secret_key_prefix: String,
+
+    // Serializes the read-modify-write cycles on the environments file
+    lock: Arc<Mutex<()>>,
+}
+
+impl EnvironmentManager {
+    // Returns the manager of the app-wide environments
+    pub fn new(storage_path: PathBuf, secret_store: Arc<dyn SecretStore>) -> Self {
+        let file_path = Path::new(&storage_path).join(ENVIRONMENT_FILE);
+        Self {
+            file_path,
+            scope: EnvironmentScope::Global,
+            secret_store,
+            secret_key_prefix: "environments".to_string(),
+            lock: Arc::new(Mutex::new(())),
+        }
+    }
+
+    // Returns the manager of the environments of the project stored at the given path
+    fn for_project(
+        project_path: &Path,
+        project_name: &str,
+        secret_store: Arc<dyn SecretStore>,
+        lock: Arc<Mutex<()>>,
+    ) -> Self {
+        Self {
+            file_path: project_path.join(ENVIRONMENT_FILE),
+            scope: EnvironmentScope::Project,
+            secret_store,
+            secret_key_prefix: format!("projects/{}/environments", project_name),
+            lock,
+        }
+    }
+
+    pub fn initialize(&self) -> io::Result<()> {
+        // if the file doesn't exist create the default environment
+        if !self.file_path.exists() {
+            let default_name = "default";
+            let default_environment =
+                Environment::new(default_name, "default environment", BTreeMap::new());
+
+            let environment_data = EnvironmentsData::new(vec![default_environment], default_name);
+
+            write_versioned(&self.file_path, &environment_data, &ENVIRONMENTS_SCHEMA)?;
+        }
+
+        Ok(())
+    }
+
+    // Loads the environments, with the values of secret variables masked.
+    pub fn load(&self) -> io::Result<EnvironmentsData> {
+        let mut environments_data = self.read()?;
+
+        for environment in environments_data.environments.iter_mut() {
+            for variable in environment.variables.values_mut() {
+                if variable.secret {
+                    variable.value = SECRET_MASK.to_string();
+                }
+            }
+        }
+
+        Ok(environments_data)
+    }
+
+    // Saves the environments, moving the values of secret variables to
+    // the secret store.
+    //
+    // Secret variables still holding their masked value keep their
+    // stored value, and the stored values of secret variables which
+    // were removed, or are no longer secret, are deleted.
+    pub fn save(&self, environments_data: &EnvironmentsData) -> io::Result<()> {
+        self.update(|current, secrets| {
+            let previous_secrets = self.secret_keys(current);
+
+            let mut to_write = EnvironmentsData::new(vec![], &environments_data.active);
+            for environment in &environments_data.environments {
+                let mut variables = BTreeMap::new();
+
+                for (name, variable) in &environment.variables {
+                    let key = self.secret_key(&environment.name, name);
+                    let variable = self.store_variable(
+                        secrets,
+                        &key,
+                        variable,
+                        previous_secrets.contains(&key),
+                    )?;
+                    variables.insert(name.clone(), variable);
+                }
+
+                to_write.environments.push(Environment::new(
+                    &environment.name,
+                    &environment.description,
+                    variables,
+                ));
+            }
+
+            let current_secrets = self.secret_keys(&to_write);
+            for key in previous_secrets.difference(&current_secrets) {
+                secrets.delete(key);
+            }
+
+            *current = to_write;
+            Ok(())
+        })
+    }
+
+    // Creates a new, empty, environment.
+    pub fn create_environment(&self, name: &str, description: &str) -> io::Result<()> {
+        self.update(|environments_data, _| {
+            if find_environment(environments_data, name).is_ok() {
+                return Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    format!("environment {} already exists", name),
+                ));
+            }
+
+            environments_data.environments.push(Environment::new(
+                name,
+                description,
+                BTreeMap::new(),
+            ));
+            Ok(())
+        })
+    }
+
+    // Renames an environment, along with the secrets of its variables.
+    pub fn rename_environment(&self, name: &str, new_name: &str) -> io::Result<()> {
+        if new_name.trim().is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the environment name can't be empty",
+            ));
+        }
+
+        self.update(|environments_data, secrets| {
+            if find_environment(environments_data, new_name).is_ok() {
+                return Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    format!("environment {} already exists", new_name),
+                ));
+            }
+
+            let environment = find_environment(environments_data, name)?;
+            for (variable_name, variable) in &environment.variables {
+                if variable.secret {
+                    let key = self.secret_key(name, variable_name);
+                    if let Some(value) = self.secret_value(secrets, &key)? {
+                        secrets.set(&self.secret_key(new_name, variable_name), &value);
+                    }
+                    secrets.delete(&key);
+                }
+            }
+            environment.name = new_name.to_string();
+
+            if environments_data.active == name {
+                environments_data.active = new_name.to_string();
+            }
+
+            Ok(())
+        })
+    }
+
+    // Deletes an environment, along with the secrets of its variables.
+    //
+    // The active global environment can't be deleted, while deleting the
+    // active environment of a project makes it fall back to the global one.
+    pub fn delete_environment(&self, name: &str) -> io::Result<()> {
+        self.update(|environments_data, secrets| {
+            if environments_data.active == name {
+                match self.scope {
+                    EnvironmentScope::Global => {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidInput,
+                            "the active environment can't be deleted",
+                        ))
+                    }
+                    EnvironmentScope::Project => environments_data.active = String::new(),
+                }
+            }
+
+            let environment = find_environment(environments_data, name)?;
+            for (variable_name, variable) in &environment.variables {
+                if variable.secret {
+                    secrets.delete(&self.secret_key(name, variable_name));
+                }
+            }
+
+            environments_data
+                .environments
+                .retain(|environment| environment.name != name);
+            Ok(())
+        })
+    }
+
+    // Sets the value of a variable of an environment, creating the
+    // variable if it doesn't exist.
+    //
+    // Setting a secret variable to its masked value only updates its
+    // secret flag, keeping its stored value.
+    pub fn set_variable(
+        &self,
+        environment_name: &str,
+        name: &str,
+        variable: &EnvironmentVariable,
+    ) -> io::Result<()> {
+        if name.trim().is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the variable name can't be empty",
+            ));
+        }
+
+        self.update(|environments_data, secrets| {
+            let key = self.secret_key(environment_name, name);
+
+            let environment = find_environment(environments_data, environment_name)?;
+            let was_secret = environment
+                .variables
+                .get(name)
+                .is_some_and(|previous| previous.secret);
+
+            let variable = self.store_variable(secrets, &key, variable, was_secret)?;
+            if was_secret && !variable.secret {
+                secrets.delete(&key);
+            }
+
+            environment.variables.insert(name.to_string(), variable);
+            Ok(())
+        })
+    }
+
+    // Removes a variable from an environment, along with its secret.
+    pub fn unset_variable(&self, environment_name: &str, name: &str) -> io::Result<()> {
+        self.update(|environments_data, secrets| {
+            let environment = find_environment(environments_data, environment_name)?;
+            let variable = environment.variables.remove(name).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("variable {} not found", name),
+                )
+            })?;
+
+            if variable.secret {
+                secrets.delete(&self.secret_key(environment_name, name));
+            }
+
+            Ok(())
+        })
+    }
+
+    // Imports variables into an environment, creating the environment if it
+    // doesn't exist. Imported variables replace the existing ones with the
+    // same names, the others are kept.
+    pub fn import_environment(
+        &self,
+        name: &str,
+        description: &str,
+        variables: &BTreeMap<String, EnvironmentVariable>,
+    ) -> io::Result<()> {
+        self.update(|environments_data, secrets| {
+            if find_environment(environments_data, name).is_err() {
+                environments_data.environments.push(Environment::new(
+                    name,
+                    description,
+                    BTreeMap::new(),
+                ));
+            }
+
+            for (variable_name, variable) in variables {
+                let key = self.secret_key(name, variable_name);
+
+                let environment = find_environment(environments_data, name)?;
+                let was_secret = environment
+                    .variables
+                    .get(variable_name)
+                    .is_some_and(|previous| previous.secret);
+
+                let variable = self.store_variable(secrets, &key, variable, was_secret)?;
+                if was_secret && !variable.secret {
+                    secrets.delete(&key);
+                }
+
+                environment
+                    .variables
+                    .insert(variable_name.clone(), variable);
+            }
+
+            Ok(())
+        })
+    }
+
+    // Returns the variables of an environment for exporting them to a file,
+    // with the actual values of its secret variables, or without its secret
+    // variables at all.
+    pub fn export_variables(
+        &self,
+        environment_name: &str,
+        include_secrets: bool,
+    ) -> io::Result<BTreeMap<String, String>> {
+        let variables = self
+            .resolve_variables(environment_name)?
+            .ok_or_else(|| environment_not_found(environment_name))?;
+
+        Ok(variables
+            .into_iter()
+            .filter(|(_, variable)| include_secrets || !variable.secret)
+            .map(|(name, variable)| (name, variable.value))
+            .collect())
+    }
+
+    // Sets the active environment.
+    //
+    // The environment must be one of the managed environments, or one of
+    // the inherited ones: the global environments a project can select.
+    // Projects can also set an empty active environment, to fall back to
+    // the global one.
+    pub fn set_active_environment(&self, name: &str, inherited: &[String]) -> io::Result<()> {
+        self.update(|environments_data, _| {
+            let inherits = self.scope == EnvironmentScope::Project && name.is_empty();
+            if !inherits && !inherited.iter().any(|inherited| inherited == name) {
+                find_environment(environments_data, name)?;
+            }
+
+            environments_data.active = name.to_string();
+            Ok(())
+        })
+    }
+
+    // Returns the names of the managed environments
+    pub fn environment_names(&self) -> io::Result<Vec<String>> {
+        Ok(self
+            .read()?
+            .environments
+            .into_iter()
+            .map(|environment| environment.name)
+            .collect())
+    }
+
+    // Returns the variables of the environment with the given name, with
+    // the actual values of its secret variables, if the environment exists.
+    //
+    // This is meant for injecting the variables into a k6 run, the
+    // returned values should never be sent to the frontend.
+    pub fn resolve_variables(
+        &self,
+        environment_name: &str,
+    ) -> io::Result<Option<BTreeMap<String, EnvironmentVariable>>> {
+        let environments_data = self.read()?;
+        let environment = match environments_data
+            .environments
+            .iter()
+            .find(|environment| environment.name == environment_name)
+        {
+            Some(environment) => environment,
+            None => return Ok(None),
+        };
+
+        let mut variables = BTreeMap::new();
+        for (name, variable) in &environment.variables {
+            let value = if variable.secret {
+                let key = self.secret_key(&environment.name, name);
+                self.secret_store.get(&key)?.unwrap_or_default()
+            } else {
+                variable.value.clone()
+            };
+
+            variables.insert(
+                name.clone(),
+                EnvironmentVariable::new(&value, variable.secret),
+            );
+        }
+
+        Ok(Some(variables))
+    }
+
+    // Applies a change to the environments, which are validated before being
+    // saved, along with the changes it makes to the secret store.
+    //
+    // The secret store is only changed once the environments file is written,
+    // and failing to change it restores both the file and the secrets as they
+    // were, so that the file never refers to secrets that weren't stored.
+    fn update<T>(
+        &self,
+        change: impl FnOnce(&mut EnvironmentsData, &mut SecretChanges) -> io::Result<T>,
+    ) -> io::Result<T> {
+        let _guard = self.lock.lock().unwrap();
+
+        let existed = self.file_path.exists();
+        let previous = self.read()?;
+
+        let mut environments_data = previous.clone();
+        let mut secrets = SecretChanges::default();
+        let result = change(&mut environments_data, &mut secrets)?;
+
+        let mut previous_secrets = BTreeMap::new();
+        for key in secrets.values.keys() {
+            previous_secrets.insert(key.clone(), self.secret_store.get(key)?);
+        }
+
+        self.write(&environments_data)?;
+
+        if let Err(error) = self.apply_secret_changes(&secrets.values) {
+            if let Err(rollback_error) = self.rollback(existed, &previous, &previous_secrets) {
+                eprintln!(
+                    "failed to restore the environments in {}: {}",
+                    self.file_path.display(),
+                    rollback_error
+                );
+            }
+            return Err(error);
+        }
+
+        Ok(result)
+    }
+
+    // Sets or deletes the given secrets, a deleted secret having no value.
+    //
+    // All the changes are attempted, the first failure being reported.
+    fn apply_secret_changes(&self, changes: &BTreeMap<String, Option<String>>) -> io::Result<()> {
+        let mut result = Ok(());
+        for (key, value) in changes {
+            let applied = match value {
+                Some(value) => self.secret_store.set(key, value),
+                None => self.secret_store.delete(key),
+            };
+            result = result.and(applied);
+        }
+
+        result
+    }
+
+    // Restores the environments file and the secrets as they were before
+    // an update which couldn't be completed.
+    fn rollback(
+        &self,
+        existed: bool,
+        previous: &EnvironmentsData,
+        previous_secrets: &BTreeMap<String, Option<String>>,
+    ) -> io::Result<()> {
+        let restored = self.apply_secret_changes(previous_secrets);
+
+        if existed {
+            write_versioned(&self.file_path, previous, &ENVIRONMENTS_SCHEMA)?;
+        } else {
+            fs::remove_file(&self.file_path)?;
+        }
+
+        restored
+    }
+
+    // Returns the variable as it is written to the environments file, moving
+    // its value to the secret store if it is a secret.
+    fn store_variable(
+        &self,
+        secrets: &mut SecretChanges,
+        key: &str,
+        variable: &EnvironmentVariable,
+        was_secret: bool,
+    ) -> io::Result<EnvironmentVariable> {
+        let variable = match (variable.secret, variable.is_masked()) {
+            (true, true) => EnvironmentVariable::new("", true),
+            (true, false) => {
+                secrets.set(key, &variable.value);
+                EnvironmentVariable::new("", true)
+            }
+            // a secret turned into a plain variable without being edited
+            (false, true) if was_secret => {
+                let value = self.secret_value(secrets, key)?.unwrap_or_default();
+                EnvironmentVariable::new(&value, false)
+            }
+            (false, _) => variable.clone(),
+        };
+
+        Ok(variable)
+    }
+
+    // Returns the value of a secret, taking the pending changes into account.
+    fn secret_value(&self, secrets: &SecretChanges, key: &str) -> io::Result<Option<String>> {
+        match secrets.values.get(key) {
+            Some(value) => Ok(value.clone()),
+            None => self.secret_store.get(key),
+        }
+    }
+
+    // Reads the environments file as is.
+    //
+    // A missing file holds no environments, which is the case of the
+    // projects which don't define any.
+    fn read(&self) -> io::Result<EnvironmentsData> {
+        if !self.file_path.exists() {
+            return Ok(EnvironmentsData::new(vec![], ""));
+        }
+
+        read_versioned(&self.file_path, &ENVIRONMENTS_SCHEMA)
+    }
+
+    // Writes the environments file, once validated
+    fn write(&self, environments_data: &EnvironmentsData) -> io::Result<()> {
+        self.validate(environments_data)?;
+
+        write_versioned(&self.file_path, environments_data, &ENVIRONMENTS_SCHEMA)
+    }
+
+    // Checks that the environments have unique, non-empty, names and that the
+    // global active environment exists.
+    //
+    // The active environment of a project isn't checked, as it can name
+    // a global environment.
+    fn validate(&self, environments_data: &EnvironmentsData) -> io::Result<()> {
+        let mut names = BTreeSet::new();
+        for environment in &environments_data.environments {
+            if environment.name.trim().is_empty() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "the environment name can't be empty",
+                ));
+            }
+
+            if !names.insert(environment.name.as_str()) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("duplicate environment {}", environment.name),
+                ));
+            }
+        }
+
+        if self.scope == EnvironmentScope::Global
+            && !names.contains(environments_data.active.as_str())
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "the active environment {} doesn't exist",
+                    environments_data.active
+                ),
+            ));
+        }
+
+        Ok(())
+    }
+
+    // Returns the key under which the value of a secret variable
+    // of the given environment is stored in the secret store.
+    fn secret_key(&self, environment_name: &str, variable_name: &str) -> String {
+        format!(
+            "{}/{}/{}",
+            self.secret_key_prefix, environment_name, variable_name
+        )
+    }
+
+    // Returns the keys of the secret store holding the values
+    // of the secret variables of the environments.
+    fn secret_keys(&self, environments_data: &EnvironmentsData) -> BTreeSet<String> {
+        environments_data
+            .environments
+            .iter()
+            .flat_map(|environment| {
+                environment
+                    .variables
+                    .iter()
+                    .filter(|(_, variable)| variable.secret)
+                    .map(|(name, _)| self.secret_key(&environment.name, name))
+            })
+            .collect()
+    }
+}
+
+// Returns the environment with the given name
+fn find_environment<'a>(
+    environments_data: &'a mut EnvironmentsData,
+    name: &str,
+) -> io::Result<&'a mut Environment> {
+    environments_data
+        .environments
+        .iter_mut()
+        .find(|environment| environment.name == name)
+        .ok_or_else(|| environment_not_found(name))
+}
+
+// Merges the environments of a project into the global ones.
+//
+// Project environments take precedence over the global environments of
+// the same name, variable by variable: a project environment only needs
+// to define the variables whose value differs from the global one.
+// The active environment of the project, if it sets one, takes precedence
+// as well.
+pub fn merge_environments(global: EnvironmentsData, project: EnvironmentsData) -> EnvironmentsData {
+    let mut merged = global;
+
+    for environment in project.environments {
+        let existing = merged
+            .environments
+            .iter_mut()
+            .find(|existing| existing.name == environment.name);
+
+        match existing {
+            Some(existing) => {
+                if !environment.description.is_empty() {
+                    existing.description = environment.description;
+                }
+                existing.variables.extend(environment.variables);
+            }
+            None => merged.environments.push(environment),
+        }
+    }
+
+    if !project.active.is_empty() {
+        merged.active = project.active;
+    }
+
+    merged
+}
+
+// Returns the name of the environment in effect for a project: the given
+// one if any, or else the project's active environment, or else the global
+// active environment.
+pub fn effective_environment_name(
+    environment_name: Option<&str>,
+    project_active: &str,
+    global_active: &str,
+) -> String {
+    match environment_name {
+        Some(environment_name) => environment_name.to_string(),
+        None if !project_active.is_empty() => project_active.to_string(),
+        None => global_active.to_string(),
+    }
+}
+
+// Returns the effective variables of an environment, as seen by a project
+// with the given environments, along with the scope each of them comes from.
+//
+// The values are the ones held by the given environments, which are
+// expected to have their secrets masked.
+pub fn effective_variables(
+    global: &EnvironmentsData,
+    project: &EnvironmentsData,
+    environment_name: &str,
+) -> io::Result<BTreeMap<String, ResolvedVariable>> {
+    let scoped = [
+        (EnvironmentScope::Global, global),
+        (EnvironmentScope::Project, project),
+    ];
+
+    let mut found = false;
+    let mut variables = BTreeMap::new();
+    for (scope, environments_data) in scoped {
+        let environment = environments_data
+            .environments
+            .iter()
+            .find(|environment| environment.name == environment_name);
+
+        if let Some(environment) = environment {
+            found = true;
+            for (name, variable) in &environment.variables {
+                variables.insert(
+                    name.clone(),
+                    ResolvedVariable {
+                        value: variable.value.clone(),
+                        secret: variable.secret,
+                        scope,
+                    },
+                );
+            }
+        }
+    }
+
+    if !found {
+        return Err(environment_not_found(environment_name));
+    }
+
+    Ok(variables)
+}
+
+// Returns an environment as seen by a project with the given environments,
+// with the references the values of its variables hold resolved, for the
+// frontend to substitute them in the scripts it generates.
+//
+// The given environments are expected to have their secrets masked: the
+// secrets, along with the variables drawing on them or on the app's
+// environment, are returned masked and flagged as secret, their value being
+// only known to k6 runs.
+pub fn interpolated_environment(
+    global: &EnvironmentsData,
+    project: &EnvironmentsData,
+    environment_name: &str,
+) -> io::Result<Environment> {
+    let effective = effective_variables(global, project, environment_name)?;
+
+    let raw: BTreeMap<String, String> = effective
+        .iter()
+        .map(|(name, variable)| (name.clone(), variable.value.clone()))
+        .collect();
+    let secrets: BTreeSet<String> = effective
+        .iter()
+        .filter(|(_, variable)| variable.secret)
+        .map(|(name, _)| name.clone())
+        .collect();
+
+    let sensitive = interpolation::sensitive_variables(&raw, &secrets);
+    let resolved = interpolation::interpolate(&raw, &secrets)?;
+
+    let variables = resolved
+        .into_iter()
+        .map(|(name, value)| {
+            let variable = if sensitive.contains(&name) {
+                EnvironmentVariable::new(SECRET_MASK, true)
+            } else {
+                EnvironmentVariable::new(&value, false)
+            };
+            (name, variable)
+        })
+        .collect();
+
+    let description = [project, global]
+        .iter()
+        .flat_map(|environments_data| &environments_data.environments)
+        .find(|environment| {
+            environment.name == environment_name && !environment.description.is_empty()
+        })
+        .map(|environment| environment.description.as_str())
+        .unwrap_or_default();
+
+    Ok(Environment::new(environment_name, description, variables))
+}
+
+// Returns the effective variables of an environment, with the actual
+// values of their secrets, as seen by the project with the given
+// environment manager if any.
+//
+// The references the values hold are resolved once the project's
+// variables are merged into the global ones, so that a project overriding
+// a variable also overrides the values derived from it. The values of
+// secrets are taken as is, and variables derived from them, or from the
+// app's environment, are flagged as secret too.
+//
+// The active environment is used when no environment name is given.
+pub fn resolve_effective_variables(
+    global: &EnvironmentManager,
+    project: Option<&EnvironmentManager>,
+    environment_name: Option<&str>,
+) -> io::Result<BTreeMap<String, EnvironmentVariable>> {
+    let project_active = match project {
+        Some(project) => project.read()?.active,
+        None => String::new(),
+    };
+    let environment_name =
+        effective_environment_name(environment_name, &project_active, &global.read()?.active);
+
+    let global_variables = global.resolve_variables(&environment_name)?;
+    let project_variables = match project {
+        Some(project) => project.resolve_variables(&environment_name)?,
+        None => None,
+    };
+
+    let variables = match (global_variables, project_variables) {
+        (None, None) => return Err(environment_not_found(&environment_name)),
+        (Some(variables), None) | (None, Some(variables)) => variables,
+        (Some(mut variables), Some(project_variables)) => {
+            variables.extend(project_variables);
+            variables
+        }
+    };
+
+    let raw: BTreeMap<String, String> = variables
+        .iter()
+        .map(|(name, variable)| (name.clone(), variable.value.clone()))
+        .collect();
+    let secrets: BTreeSet<String> = variables
+        .iter()
+        .filter(|(_, variable)| variable.secret)
+        .map(|(name, _)| name.clone())
+        .collect();
+
+    let sensitive = interpolation::sensitive_variables(&raw, &secrets);
+    let resolved = interpolation::interpolate(&raw, &secrets)?;
+
+    Ok(resolved
+        .into_iter()
+        .map(|(name, value)| {
+            let secret = sensitive.contains(&name);
+            (name, EnvironmentVariable::new(&value, secret))
+        })
+        .collect())
+}
+
+// The schema of a kind of JSON file the app stores.
+//
+// Files are written along with the version of their schema, and files which
+// don't hold one are at the base version of their schema. Each migration
+// upgrades a file from one version to the next, so the current version of
+// a schema is its base version plus its number of migrations.
+struct Schema {
+    // The version of files which don't hold theirs: 0 for the files written
+    // before schemas were versioned, 1 for the ones which always were.
+    base_version: u64,
+
+    migrations: &'static [Migration],
+}
+
+// A migration upgrades a JSON document to the next version of its schema
+type Migration = fn(&mut Map<String, Value>) -> io::Result<()>;
+
+impl Schema {
+    fn current_version(&self) -> u64 {
+        self.base_version + self.migrations.len() as u64
+    }
+}
+
+// The schema of environments files, global or project ones
+const ENVIRONMENTS_SCHEMA: Schema = Schema {
+    base_version: 0,
+    migrations: &[migrate_plain_variables],
+};
+
+// The schema of project configuration files, which didn't change
+// since before schemas were versioned.
+const PROJECT_CONFIG_SCHEMA: Schema = Schema {
+    base_version: 1,
+    migrations: &[],
+};
+
+// The schema of recorder sessions files
+const RECORDING_SCHEMA: Schema = Schema {
+    base_version: 1,
+    migrations: &[],
+};
+
+// The schema of test metadata files
+const TEST_METADATA_SCHEMA: Schema = Schema {
+    base_version: 1,
+    migrations: &[],
+};
+
+// The schema of project files
+const PROJECT_SCHEMA: Schema = Schema {
+    base_version: 1,
+    migrations: &[],
+};
+
+// The schema of suite files
+const SUITE_SCHEMA: Schema = Schema {
+    base_version: 1,
+    migrations: &[],
+};
+
+// Variables used to be plain strings, before they could be marked as secret
+fn migrate_plain_variables(document: &mut Map<String, Value>) -> io::Result<()> {
+    let environments = document
+        .get_mut("environments")
+        .and_then(Value::as_array_mut)
+        .into_iter()
+        .flatten();
+
+    for environment in environments {
+        let variables = environment
+            .get_mut("variables")
+            .and_then(Value::as_object_mut)
+            .into_iter()
+            .flat_map(|variables| variables.values_mut());
+
+        for variable in variables {
+            if let Value::String(value) = variable {
+                *variable = json!({ "value": value, "secret": false });
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// Reads the JSON file at the given path, upgrading its content to the
+// current version of its schema.
+//
+// The file itself is left as is, it's upgraded the next time it's written.
+fn read_versioned<T: DeserializeOwned>(path: &Path, schema: &Schema) -> io::Result<T> {
+    let invalid = |message: String| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{}: {}", path.display(), message),
+        )
+    };
+
+    let (mut document, version) = read_document(path, schema)?;
+    if version > schema.current_version() {
+        return Err(invalid(format!(
+            "schema version {} is newer than the supported one, {}",
+            version,
+            schema.current_version()
+        )));
+    }
+
+    let applied = (version - schema.base_version) as usize;
+    for migration in &schema.migrations[applied..] {
+        migration(&mut document)?;
+    }
+
+    document.remove(SCHEMA_VERSION_FIELD);
+    serde_json::from_value(Value::Object(document))
+        .map_err(|e| invalid(format!("invalid content: {}", e)))
+}
+
+// Reads the JSON file at the given path, along with the version of its schema.
+fn read_document(path: &Path, schema: &Schema) -> io::Result<(Map<String, Value>, u64)> {
+    let invalid = |message: &str| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{}: {}", path.display(), message),
+        )
+    };
+
+    let file = fs::File::open(path)?;
+    let document = match serde_json::from_reader(file)? {
+        Value::Object(document) => document,
+        _ => return Err(invalid("expected a JSON object")),
+    };
+
+    let version = match document.get(SCHEMA_VERSION_FIELD) {
+        None => schema.base_version,
+        Some(version) => version
+            .as_u64()
+            .filter(|version| *version >= schema.base_version)
+            .ok_or_else(|| invalid("invalid schema version"))?,
+    };
+
+    Ok((document, version))
+}
+
+// Writes the value to the JSON file at the given path, along with the
+// current version of its schema.
+//
+// A file written with an earlier version of its schema is backed up before
+// being upgraded, so that it can be restored by downgrading the app.
+fn write_versioned<T: Serialize>(path: &Path, value: &T, schema: &Schema) -> io::Result<()> {
+    let mut document = match serde_json::to_value(value)? {
+        Value::Object(document) => document,
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "only JSON objects can be versioned",
+            ))
+        }
+    };
+    document.insert(
+        SCHEMA_VERSION_FIELD.to_string(),
+        schema.current_version().into(),
+    );
+
+    // files which can't be read are overwritten, there's nothing to upgrade
+    if let Ok((_, version)) = read_document(path, schema) {
+        if version < schema.current_version() {
+            back_up(path, version)?;
+        }
+    }
+
+    storage::write_json_atomic(path, &document)
+}
+
+// Copies a file about to be upgraded to the backups directory next to it,
+// suffixed with the version of its schema.
+fn back_up(path: &Path, version: u64) -> io::Result<()> {
+    let (directory, file_name) = match (path.parent(), path.file_name()) {
+        (Some(directory), Some(file_name)) => (directory, file_name.to_string_lossy()),
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid file path",
+            ))
+        }
+    };
+
+    let backups_dir = directory.join(BACKUPS_DIR);
+    if !backups_dir.exists() {
+        fs::create_dir(&backups_dir)?;
+    }
+
+    fs::copy(
+        path,
+        backups_dir.join(format!("{}.v{}", file_name, version)),
+    )?;
+    Ok(())
+}
+
+// The file describing a project, stored at its root
+#[derive(Default, Serialize, Deserialize)]
+struct ProjectFile {
+    #[serde(default)]
+    description: Option<String>,
+}
+
+// A test file of a project
+struct TestFile {
+    name: String,
+    kind: TestKind,
+    path: PathBuf,
+}
+
+// Returns the test files stored in the given project directory.
+//
+// Tests are stored as files named after them, with an extension telling
+// their kind. Other files, such as the JSON files holding the project's
+// configuration, and hidden files, which are leftovers of interrupted
+// writes, aren't tests.
+fn test_files(project_path: &Path) -> io::Result<Vec<TestFile>> {
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+
+    let mut test_files = vec![];
+    for entry in fs::read_dir(project_path)? {
+        let entry = entry?;
+        let path = entry.path();
+
+        let hidden = entry.file_name().to_string_lossy().starts_with('.');
+        if !path.is_file() || hidden {
+            continue;
+        }
+
+        let name = path
+            .file_stem()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| invalid("invalid file name"))?
+            .to_string();
+
+        let kind = path
+            .extension()
+            .and_then(|kind| kind.to_str())
+            .and_then(|kind| TestKind::from_str(kind).ok());
+
+        if let Some(kind) = kind {
+            test_files.push(TestFile { name, kind, path });
+        }
+    }
+
+    Ok(test_files)
+}
+
+// Returns the suite files stored in the given project directory
+fn suite_files(project_path: &Path) -> io::Result<Vec<PathBuf>> {
+    let suites_dir = project_path.join(SUITES_DIR);
+    if !suites_dir.exists() {
+        return Ok(vec![]);
+    }
+
+    let mut suite_files = vec![];
+    for entry in fs::read_dir(suites_dir)? {
+        let path = entry?.path();
+        if path.is_file() && path.extension().and_then(|ext| ext.to_str()) == Some("json") {
+            suite_files.push(path);
+        }
+    }
+
+    Ok(suite_files)
+}
+
+// Reads the file describing the project stored in the given directory.
+//
+// Projects created before they had one, or whose file can't be read,
+// have no description.
+fn read_project_file(project_path: &Path) -> ProjectFile {
+    let project_file_path = project_path.join(PROJECT_FILE);
+    if !project_file_path.exists() {
+        return ProjectFile::default();
+    }
+
+    read_versioned(&project_file_path, &PROJECT_SCHEMA).unwrap_or_else(|e| {
+        eprintln!("failed to read {}: {}", project_file_path.display(), e);
+        ProjectFile::default()
+    })
+}
+
+// Returns when the project stored in the given directory was last changed,
+// given the paths of its tests and suites.
+fn last_modified(project_path: &Path, paths: &[PathBuf]) -> Option<DateTime<Utc>> {
+    [PROJECT_FILE, PROJECT_CONFIG_FILE, ENVIRONMENT_FILE]
+        .iter()
+        .map(|file| project_path.join(file))
+        .chain(paths.iter().cloned())
+        .filter_map(|path| modified_at(&path))
+        .max()
+}
+
+// Returns when the file at the given path was last modified, if it exists
+fn modified_at(path: &Path) -> Option<DateTime<Utc>> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .map(DateTime::<Utc>::from)
+}
+
+fn environment_not_found(environment_name: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("environment {} not found", environment_name),
+    )
+}
+
+// Returns the key under which the cloud token of the given project
+// is stored in the secret store.
+fn project_cloud_token_key(project_name: &str) -> String {
+    format!("projects/{}/cloud_token", project_name)
+}
+
+// Returns the path of the file with the given basename in the given directory, if it exists.
+//
+// This function allows to check if a test file with the provided `basename` exists in the
+// project directory, regardless of its extension. Only files with a test extension are
+// considered, so that a test named after one of the project's JSON files (e.g. `environments`)
+// doesn't resolve to it.
+fn get_file_with_basename(directory: &Path, basename: &str) -> Option<PathBuf> {
+    if directory.is_dir() {
+        if let Ok(entries) = fs::read_dir(directory) {
+            for entry in entries.flatten() {
+                let path = entry.path();
+                let is_test = path
+                    .extension()
+                    .and_then(|kind| kind.to_str())
+                    .is_some_and(|kind| TestKind::from_str(kind).is_ok());
+                if path.is_file() && is_test && path.file_stem().is_some_and(|s| s == basename) {
+                    return Some(path);
+                }
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{StepOptions, SuiteMode, SuiteStep};
+    use crate::secrets::MemorySecretStore;
+
+    fn environment_manager(
+        storage_path: &Path,
+        secret_store: &Arc<MemorySecretStore>,
+    ) -> EnvironmentManager {
+        let environment_manager =
+            EnvironmentManager::new(storage_path.to_path_buf(), secret_store.clone());
+        environment_manager.initialize().unwrap();
+        environment_manager
+    }
+
+    fn secret(value: &str) -> EnvironmentVariable {
+        EnvironmentVariable::new(value, true)
+    }
+
+    #[test]
+    fn secret_variables_are_kept_out_of_the_environments_file() {
+        let storage = tempfile::tempdir().unwrap();
+        let secret_store = Arc::new(MemorySecretStore::new());
+        let environment_manager = environment_manager(storage.path(), &secret_store);
+
+        environment_manager
+            .set_variable("default", "TOKEN", &secret("s3cret"))
+            .unwrap();
+
+        let file = fs::read_to_string(storage.path().join(ENVIRONMENT_FILE)).unwrap();
+        assert!(!file.contains("s3cret"));
+        assert_eq!(
+            secret_store.get("environments/default/TOKEN").unwrap(),
+            Some("s3cret".to_string())
+        );
+
+        let loaded = environment_manager.load().unwrap();
+        assert_eq!(
+            loaded.environments[0].variables["TOKEN"],
+            secret(SECRET_MASK)
+        );
+
+        let resolved = environment_manager.resolve_variables("default").unwrap();
+        assert_eq!(resolved.unwrap()["TOKEN"], secret("s3cret"));
+    }
+
+    #[test]
+    fn saving_masked_secrets_keeps_their_value() {
+        let storage = tempfile::tempdir().unwrap();
+        let secret_store = Arc::new(MemorySecretStore::new());
+        let environment_manager = environment_manager(storage.path(), &secret_store);
+
+        environment_manager
+            .set_variable("default", "TOKEN", &secret("s3cret"))
+            .unwrap();
+        environment_manager
+            .set_variable("default", "OTHER", &secret("other"))
+            .unwrap();
+
+        // the frontend sends back the masked values of the secrets it didn't change
+        let mut environments_data = environment_manager.load().unwrap();
+        environments_data.environments[0].variables.remove("OTHER");
+        environment_manager.save(&environments_data).unwrap();
+
+        assert_eq!(
+            secret_store.get("environments/default/TOKEN").unwrap(),
+            Some("s3cret".to_string())
+        );
+        assert_eq!(
+            secret_store.get("environments/default/OTHER").unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn secrets_turned_into_plain_variables_leave_the_secret_store() {
+        let storage = tempfile::tempdir().unwrap();
+        let secret_store = Arc::new(MemorySecretStore::new());
+        let environment_manager = environment_manager(storage.path(), &secret_store);
+
+        environment_manager
+            .set_variable("default", "TOKEN", &secret("s3cret"))
+            .unwrap();
+        environment_manager
+            .set_variable(
+                "default",
+                "TOKEN",
+                &EnvironmentVariable::new(SECRET_MASK, false),
+            )
+            .unwrap();
+
+        let loaded = environment_manager.load().unwrap();
+        assert_eq!(
+            loaded.environments[0].variables["TOKEN"],
+            EnvironmentVariable::new("s3cret", false)
+        );
+        assert_eq!(
+            secret_store.get("environments/default/TOKEN").unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn renaming_an_environment_moves_its_secrets() {
+        let storage = tempfile::tempdir().unwrap();
+        let secret_store = Arc::new(MemorySecretStore::new());
+        let environment_manager = environment_manager(storage.path(), &secret_store);
+
+        environment_manager
+            .set_variable("default", "TOKEN", &secret("s3cret"))
+            .unwrap();
+        environment_manager
+            .rename_environment("default", "staging")
+            .unwrap();
+
+        assert_eq!(
+            secret_store.get("environments/default/TOKEN").unwrap(),
+            None
+        );
+        assert_eq!(
+            secret_store.get("environments/staging/TOKEN").unwrap(),
+            Some("s3cret".to_string())
+        );
+    }
+
+    #[test]
+    fn plaintext_cloud_tokens_are_moved_to_the_secret_store() {
+        let storage = tempfile::tempdir().unwrap();
+        let secret_store = Arc::new(MemorySecretStore::new());
+        let project_manager =
+            LocalProjectManager::new(storage.path().to_path_buf(), secret_store.clone());
+        project_manager.initialize().unwrap();
+
+        let project_config_path = project_manager
+            .project_path(DEFAULT_PROJECT_NAME)
+            .join(PROJECT_CONFIG_FILE);
+        fs::write(
+            &project_config_path,
+            r#"{"cloud_token": "t0ken", "cloud_project_id": "42"}"#,
+        )
+        .unwrap();
+
+        // reading the configuration leaves the file as it is
+        let project_config = project_manager
+            .load_project_config(DEFAULT_PROJECT_NAME)
+            .unwrap();
+        assert_eq!(project_config.cloud_token.as_deref(), Some("t0ken"));
+        let file = fs::read_to_string(&project_config_path).unwrap();
+        assert!(file.contains("t0ken"));
+
+        project_manager.initialize().unwrap();
+
+        let project_config = project_manager
+            .load_project_config(DEFAULT_PROJECT_NAME)
+            .unwrap();
+        assert_eq!(project_config.cloud_token.as_deref(), Some("t0ken"));
+        assert_eq!(project_config.cloud_project_id.as_deref(), Some("42"));
+
+        let file = fs::read_to_string(&project_config_path).unwrap();
+        assert!(!file.contains("t0ken"));
+        assert_eq!(
+            secret_store
+                .get(&project_cloud_token_key(DEFAULT_PROJECT_NAME))
+                .unwrap(),
+            Some("t0ken".to_string())
+        );
+    }
+
+    #[test]
+    fn resolved_variables_derived_from_secrets_are_secret() {
+        let storage = tempfile::tempdir().unwrap();
+        let secret_store = Arc::new(MemorySecretStore::new());
+        let environment_manager = environment_manager(storage.path(), &secret_store);
+
+        // secrets are taken as is, even when they look like references
+        environment_manager
+            .set_variable("default", "TOKEN", &secret("s3${cret"))
+            .unwrap();
+        for (name, value) in [("AUTH", "Bearer ${TOKEN}"), ("HOST", "example.com")] {
+            environment_manager
+                .set_variable("default", name, &EnvironmentVariable::new(value, false))
+                .unwrap();
+        }
+
+        let variables = resolve_effective_variables(&environment_manager, None, None).unwrap();
+        assert_eq!(variables["TOKEN"], secret("s3${cret"));
+        assert_eq!(variables["AUTH"], secret("Bearer s3${cret"));
+        assert_eq!(
+            variables["HOST"],
+            EnvironmentVariable::new("example.com", false)
+        );
+    }
+
+    #[test]
+    fn explicit_environments_take_precedence_over_the_active_ones() {
+        assert_eq!(
+            effective_environment_name(Some("staging"), "local", "default"),
+            "staging"
+        );
+        assert_eq!(
+            effective_environment_name(None, "local", "default"),
+            "local"
+        );
+        assert_eq!(effective_environment_name(None, "", "default"), "default");
+    }
+
+    #[test]
+    fn interpolated_environments_mask_the_values_drawing_on_secrets() {
+        let global = EnvironmentsData::new(
+            vec![Environment::new(
+                "default",
+                "",
+                BTreeMap::from([
+                    (
+                        "HOST".to_string(),
+                        EnvironmentVariable::new("example.com", false),
+                    ),
+                    (
+                        "URL".to_string(),
+                        EnvironmentVariable::new("https://${HOST}", false),
+                    ),
+                    ("TOKEN".to_string(), secret(SECRET_MASK)),
+                ]),
+            )],
+            "default",
+        );
+        let project = EnvironmentsData::new(
+            vec![Environment::new(
+                "default",
+                "",
+                BTreeMap::from([
+                    (
+                        "HOST".to_string(),
+                        EnvironmentVariable::new("test.k6.io", false),
+                    ),
+                    (
+                        "AUTH".to_string(),
+                        EnvironmentVariable::new("Bearer ${TOKEN}", false),
+                    ),
+                ]),
+            )],
+            "",
+        );
+
+        let environment = interpolated_environment(&global, &project, "default").unwrap();
+        let variables = &environment.variables;
+        assert_eq!(
+            variables["URL"],
+            EnvironmentVariable::new("https://test.k6.io", false)
+        );
+        assert_eq!(variables["TOKEN"], secret(SECRET_MASK));
+        assert_eq!(variables["AUTH"], secret(SECRET_MASK));
+    }
+
+    // A secret store refusing to store the secrets of the variables named BROKEN
+    struct BrokenSecretStore(MemorySecretStore);
+
+    impl SecretStore for BrokenSecretStore {
+        fn get(&self, key: &str) -> io::Result<Option<String>> {
+            self.0.get(key)
+        }
+
+        fn set(&self, key: &str, secret: &str) -> io::Result<()> {
+            if key.ends_with("/BROKEN") {
+                return Err(io::Error::other("secret store unavailable"));
+            }
+            self.0.set(key, secret)
+        }
+
+        fn delete(&self, key: &str) -> io::Result<()> {
+            self.0.delete(key)
+        }
+    }
+
+    #[test]
+    fn failing_to_store_secrets_leaves_the_environments_unchanged() {
+        let storage = tempfile::tempdir().unwrap();
+        let secret_store = Arc::new(BrokenSecretStore(MemorySecretStore::new()));
+        let environment_manager =
+            EnvironmentManager::new(storage.path().to_path_buf(), secret_store.clone());
+        environment_manager.initialize().unwrap();
+        environment_manager
+            .set_variable("default", "TOKEN", &secret("s3cret"))
+            .unwrap();
+
+        let environments_path = storage.path().join(ENVIRONMENT_FILE);
+        let file = fs::read_to_string(&environments_path).unwrap();
+
+        let mut environments_data = environment_manager.load().unwrap();
+        let variables = &mut environments_data.environments[0].variables;
+        variables.insert("TOKEN".to_string(), secret("n3w"));
+        variables.insert("BROKEN".to_string(), secret("0ops"));
+        assert!(environment_manager.save(&environments_data).is_err());
+
+        assert_eq!(fs::read_to_string(&environments_path).unwrap(), file);
+        assert_eq!(
+            secret_store.get("environments/default/TOKEN").unwrap(),
+            Some("s3cret".to_string())
+        );
+    }
+
+    #[test]
+    fn legacy_files_are_only_upgraded_when_written() {
+        let storage = tempfile::tempdir().unwrap();
+        let secret_store = Arc::new(MemorySecretStore::new());
+        let environments_path = storage.path().join(ENVIRONMENT_FILE);
+        let legacy = r#"{"environments": [{"name": "default", "description": "", "variables": {"HOST": "example.com"}}], "active": "default"}"#;
+        fs::write(&environments_path, legacy).unwrap();
+
+        let environment_manager = environment_manager(storage.path(), &secret_store);
+        let environments_data = environment_manager.load().unwrap();
+        assert_eq!(
+            environments_data.environments[0].variables["HOST"],
+            EnvironmentVariable::new("example.com", false)
+        );
+        assert_eq!(fs::read_to_string(&environments_path).unwrap(), legacy);
+
+        environment_manager.save(&environments_data).unwrap();
+        let backup_path = storage
+            .path()
+            .join(BACKUPS_DIR)
+            .join(format!("{}.v0", ENVIRONMENT_FILE));
+        assert_eq!(fs::read_to_string(backup_path).unwrap(), legacy);
+
+        let document: Value =
+            serde_json::from_str(&fs::read_to_string(&environments_path).unwrap()).unwrap();
+        assert_eq!(document[SCHEMA_VERSION_FIELD], 1);
+        assert_eq!(
+            document["environments"][0]["variables"]["HOST"],
+            json!({ "value": "example.com", "secret": false })
+        );
+    }
+
+    #[test]
+    fn unversioned_files_of_new_schemas_are_at_their_base_version() {
+        let storage = tempfile::tempdir().unwrap();
+        let path = storage.path().join("suite.json");
+        fs::write(&path, r#"{"name": "smoke", "steps": []}"#).unwrap();
+
+        let suite: Suite = read_versioned(&path, &SUITE_SCHEMA).unwrap();
+        assert_eq!(suite.name, "smoke");
+        assert_eq!(SUITE_SCHEMA.current_version(), 1);
+
+        fs::write(
+            &path,
+            r#"{"schema_version": 0, "name": "smoke", "steps": []}"#,
+        )
+        .unwrap();
+        assert!(read_versioned::<Suite>(&path, &SUITE_SCHEMA).is_err());
+    }
+
+    #[test]
+    fn tests_have_a_metadata_sidecar_file() {
+        let storage = tempfile::tempdir().unwrap();
+        let secret_store = Arc::new(MemorySecretStore::new());
+        let project_manager =
+            LocalProjectManager::new(storage.path().to_path_buf(), secret_store.clone());
+        project_manager.initialize().unwrap();
+
+        let mut test = Test::new("checkout", TestKind::Javascript, "export default {}");
+        let mut metadata = TestMetadata::new(Utc::now());
+        metadata.tags = vec![" smoke".to_string(), "payments".to_string()];
+        metadata.last_run_status = Some(StepStatus::Passed);
+        test.metadata = Some(metadata);
+        let created = project_manager
+            .create_test(DEFAULT_PROJECT_NAME, test)
+            .unwrap();
+
+        // only the description, tags and owner are taken from the test
+        let metadata = created.metadata.unwrap();
+        assert_eq!(metadata.tags, vec!["payments", "smoke"]);
+        assert_eq!(metadata.last_run_status, None);
+
+        let metadata_path = project_manager.test_metadata_path(DEFAULT_PROJECT_NAME, "checkout");
+        assert!(metadata_path.exists());
+
+        project_manager
+            .record_test_run(DEFAULT_PROJECT_NAME, "checkout", StepStatus::Failed)
+            .unwrap();
+        let metadata = project_manager
+            .get_test_metadata(DEFAULT_PROJECT_NAME, "checkout")
+            .unwrap();
+        assert_eq!(metadata.last_run_status, Some(StepStatus::Failed));
+        assert!(metadata.last_run_at.is_some());
+
+        project_manager
+            .delete_test(DEFAULT_PROJECT_NAME, "checkout")
+            .unwrap();
+        assert!(!metadata_path.exists());
+    }
+
+    #[test]
+    fn tests_are_filtered_by_tag_regardless_of_case() {
+        let storage = tempfile::tempdir().unwrap();
+        let secret_store = Arc::new(MemorySecretStore::new());
+        let project_manager =
+            LocalProjectManager::new(storage.path().to_path_buf(), secret_store.clone());
+        project_manager.initialize().unwrap();
+
+        for (name, tags) in [("login", vec!["Smoke"]), ("checkout", vec!["nightly"])] {
+            let test = Test::new(name, TestKind::Javascript, "export default {}");
+            project_manager
+                .create_test(DEFAULT_PROJECT_NAME, test)
+                .unwrap();
+            project_manager
+                .update_test_metadata(
+                    DEFAULT_PROJECT_NAME,
+                    name,
+                    TestMetadataUpdate {
+                        tags: Some(tags.into_iter().map(String::from).collect()),
+                        ..TestMetadataUpdate::default()
+                    },
+                )
+                .unwrap();
+        }
+
+        // tests without a sidecar file are listed with default metadata
+        fs::write(
+            project_manager
+                .project_path(DEFAULT_PROJECT_NAME)
+                .join("legacy.js"),
+            "export default {}",
+        )
+        .unwrap();
+
+        let names = |tag: Option<&str>| -> Vec<String> {
+            let mut names: Vec<String> = project_manager
+                .list_tests(DEFAULT_PROJECT_NAME, tag)
+                .unwrap()
+                .into_iter()
+                .map(|test| test.name)
+                .collect();
+            names.sort();
+            names
+        };
+        assert_eq!(names(Some("smoke")), vec!["login"]);
+        assert_eq!(names(Some(" NIGHTLY")), vec!["checkout"]);
+        assert!(names(Some("payments")).is_empty());
+        assert_eq!(names(None), vec!["checkout", "legacy", "login"]);
+    }
+
+    #[test]
+    fn suites_are_saved_and_listed() {
+        let storage = tempfile::tempdir().unwrap();
+        let secret_store = Arc::new(MemorySecretStore::new());
+        let project_manager =
+            LocalProjectManager::new(storage.path().to_path_buf(), secret_store.clone());
+        project_manager.initialize().unwrap();
+
+        assert!(project_manager
+            .list_suites(DEFAULT_PROJECT_NAME)
+            .unwrap()
+            .is_empty());
+
+        let test = Test::new("login", TestKind::Javascript, "export default {}");
+        project_manager
+            .create_test(DEFAULT_PROJECT_NAME, test)
+            .unwrap();
+
+        let mut suite = Suite {
+            name: "checkout".to_string(),
+            mode: SuiteMode::Parallel,
+            steps: vec![SuiteStep {
+                test_name: "login".to_string(),
+                options: StepOptions {
+                    vus: Some(5),
+                    continue_on_failure: true,
+                    ..StepOptions::default()
+                },
+            }],
+        };
+        project_manager
+            .save_suite(DEFAULT_PROJECT_NAME, &suite)
+            .unwrap();
+
+        let saved = project_manager
+            .get_suite(DEFAULT_PROJECT_NAME, "checkout")
+            .unwrap();
+        assert_eq!(saved.mode, SuiteMode::Parallel);
+        assert_eq!(saved.steps[0].test_name, "login");
+        assert_eq!(saved.steps[0].options.vus, Some(5));
+        assert!(saved.steps[0].options.continue_on_failure);
+
+        let suites = project_manager.list_suites(DEFAULT_PROJECT_NAME).unwrap();
+        let names: Vec<&str> = suites.iter().map(|suite| suite.name.as_str()).collect();
+        assert_eq!(names, vec!["checkout"]);
+
+        // steps have to reference tests of the project
+        suite.steps[0].test_name = "missing".to_string();
+        let error = project_manager
+            .save_suite(DEFAULT_PROJECT_NAME, &suite)
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::NotFound);
+
+        // names end up in file names
+        for name in ["", " ", ".hidden", "../escape", "a\\b"] {
+            suite.name = name.to_string();
+            suite.steps.clear();
+            let error = project_manager
+                .save_suite(DEFAULT_PROJECT_NAME, &suite)
+                .unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        }
+
+        project_manager
+            .delete_suite(DEFAULT_PROJECT_NAME, "checkout")
+            .unwrap();
+        assert!(project_manager
+            .list_suites(DEFAULT_PROJECT_NAME)
+            .unwrap()
+            .is_empty());
+        let error = project_manager
+            .delete_suite(DEFAULT_PROJECT_NAME, "checkout")
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn unreadable_files_are_left_out_of_their_project() {
+        let storage = tempfile::tempdir().unwrap();
+        let secret_store = Arc::new(MemorySecretStore::new());
+        let project_manager =
+            LocalProjectManager::new(storage.path().to_path_buf(), secret_store.clone());
+        project_manager.initialize().unwrap();
+
+        let test = Test::new("checkout", TestKind::Javascript, "export default {}");
+        project_manager
+            .create_test(DEFAULT_PROJECT_NAME, test)
+            .unwrap();
+        let suite = Suite {
+            name: "smoke".to_string(),
+            mode: SuiteMode::Sequential,
+            steps: vec![],
+        };
+        project_manager
+            .save_suite(DEFAULT_PROJECT_NAME, &suite)
+            .unwrap();
+
+        let project_path = project_manager.project_path(DEFAULT_PROJECT_NAME);
+        fs::write(project_path.join(PROJECT_FILE), "not JSON").unwrap();
+        fs::write(project_path.join("README.md"), "not a test").unwrap();
+        fs::write(
+            project_path.join(SUITES_DIR).join("broken.json"),
+            "not JSON",
+        )
+        .unwrap();
+        fs::create_dir_all(project_path.join(METADATA_DIR)).unwrap();
+        fs::write(
+            project_path.join(METADATA_DIR).join("checkout.json"),
+            "not JSON",
+        )
+        .unwrap();
+
+        let projects = project_manager.list_projects().unwrap();
+        assert_eq!(projects.len(), 1);
+        assert_eq!(projects[0].name, DEFAULT_PROJECT_NAME);
+        assert_eq!(projects[0].description, None);
+        assert_eq!(projects[0].test_count, 1);
+        assert_eq!(projects[0].suite_count, 2);
+
+        let project = project_manager.get_project(DEFAULT_PROJECT_NAME).unwrap();
+        assert_eq!(project.test_count, 1);
+        assert_eq!(project.suite_count, 1);
+        let names: Vec<&str> = project
+            .test_collections
+            .iter()
+            .map(|collection| match collection {
+                TestCollection::Test(test) => test.name.as_str(),
+                TestCollection::Suite(suite) => suite.name.as_str(),
+            })
+            .collect();
+        assert_eq!(names, vec!["checkout", "smoke"]);
+    }
+
+    #[test]
+    fn listed_projects_leave_their_configuration_out() {
+        let storage = tempfile::tempdir().unwrap();
+        let secret_store = Arc::new(MemorySecretStore::new());
+        let project_manager =
+            LocalProjectManager::new(storage.path().to_path_buf(), secret_store.clone());
+        project_manager.initialize().unwrap();
+
+        let mut project_config = ProjectConfig::new();
+        project_config.cloud_token = Some("t0ken".to_string());
+        project_manager
+            .save_project_config(DEFAULT_PROJECT_NAME, project_config)
+            .unwrap();
+
+        let projects = project_manager.list_projects().unwrap();
+        assert!(projects
+            .iter()
+            .all(|project| project.project_config.is_none()));
+
+        let project = project_manager.get_project(DEFAULT_PROJECT_NAME).unwrap();
+        let project_config = project.project_config.unwrap();
+        assert_eq!(project_config.cloud_token.as_deref(), Some("t0ken"));
+    }
+
+    #[test]
+    fn tests_named_after_project_files_are_not_confused_with_them() {
+        let storage = tempfile::tempdir().unwrap();
+        let secret_store = Arc::new(MemorySecretStore::new());
+        let project_manager =
+            LocalProjectManager::new(storage.path().to_path_buf(), secret_store.clone());
+        project_manager.initialize().unwrap();
+
+        let environments_path = project_manager
+            .project_path(DEFAULT_PROJECT_NAME)
+            .join(ENVIRONMENT_FILE);
+        fs::write(&environments_path, "{}").unwrap();
+
+        let test = Test::new("environments", TestKind::Javascript, "export default {}");
+        project_manager
+            .create_test(DEFAULT_PROJECT_NAME, test)
+            .unwrap();
+        project_manager
+            .save_test(
+                DEFAULT_PROJECT_NAME,
+                "environments",
+                "export default () => {}",
+            )
+            .unwrap();
+
+        let test = project_manager
+            .get_test(DEFAULT_PROJECT_NAME, "environments")
+            .unwrap();
+        assert!(matches!(test.kind, TestKind::Javascript));
+        assert_eq!(test.content, "export default () => {}");
+        assert_eq!(fs::read_to_string(&environments_path).unwrap(), "{}");
     }
-    None
 }
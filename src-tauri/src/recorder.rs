@@ -0,0 +1,221 @@
+use std::collections::HashSet;
+use std::process::Child;
+use std::time::Duration;
+
+use sysinfo::{Pid, System};
+use tauri::api::process::{CommandChild, CommandEvent};
+use tauri::async_runtime::Receiver;
+use tokio::net::TcpStream;
+use tokio::time::{self, Instant};
+
+// How often the proxy port is probed while waiting for the proxy to start
+const READINESS_PROBE_INTERVAL: Duration = Duration::from_millis(100);
+
+// How many lines of the proxy's output are kept to explain a failed startup
+const OUTPUT_TAIL_LINES: usize = 20;
+
+// RecorderSession owns the processes of a recorder session: the mitmdump
+// proxy, and the browser whose traffic goes through it.
+//
+// Both of them spawn processes of their own, so stopping the session kills
+// their whole process trees, as identified from their PIDs. Only processes
+// started by the session are ever killed, never unrelated instances of
+// mitmdump or of the browser.
+//
+// The session is stopped when dropped, so that its processes can't outlive it.
+pub struct RecorderSession {
+    proxy: Option<CommandChild>,
+    browser: Option<Child>,
+}
+
+impl RecorderSession {
+    pub fn new(proxy: CommandChild) -> Self {
+        Self {
+            proxy: Some(proxy),
+            browser: None,
+        }
+    }
+
+    // Attaches the browser launched for the session
+    pub fn attach_browser(&mut self, browser: Child) {
+        self.browser = Some(browser);
+    }
+
+    // Stops the session, killing the proxy and the browser along with all
+    // the processes they spawned.
+    //
+    // Stopping a session that is already stopped does nothing.
+    pub fn stop(&mut self) {
+        let mut root_pids = vec![];
+        if let Some(proxy) = &self.proxy {
+            root_pids.push(proxy.pid());
+        }
+        if let Some(browser) = &self.browser {
+            root_pids.push(browser.id());
+        }
+
+        if root_pids.is_empty() {
+            return;
+        }
+
+        // the descendants have to be found while their parents are alive,
+        // as they get adopted by another process once their parent exits.
+        let descendants = descendant_pids(&root_pids);
+
+        if let Some(mut browser) = self.browser.take() {
+            if let Err(e) = browser.kill() {
+                eprintln!("failed to kill the browser process: {}", e);
+            }
+            let _ = browser.wait();
+        }
+
+        if let Some(proxy) = self.proxy.take() {
+            if let Err(e) = proxy.kill() {
+                eprintln!("failed to kill the proxy process: {}", e);
+            }
+        }
+
+        kill_processes(&descendants);
+    }
+}
+
+impl Drop for RecorderSession {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+// Returns the PIDs of all the processes descending from the given ones
+fn descendant_pids(root_pids: &[u32]) -> Vec<Pid> {
+    let mut sys = System::new();
+    sys.refresh_processes();
+
+    let mut tree: HashSet<Pid> = root_pids.iter().map(|pid| Pid::from_u32(*pid)).collect();
+    let mut descendants = vec![];
+
+    // walk down the tree one generation at a time, until no new process joins it
+    loop {
+        let children: Vec<Pid> = sys
+            .processes()
+            .iter()
+            .filter(|(pid, process)| {
+                !tree.contains(pid)
+                    && process
+                        .parent()
+                        .is_some_and(|parent| tree.contains(&parent))
+            })
+            .map(|(pid, _)| *pid)
+            .collect();
+
+        if children.is_empty() {
+            break;
+        }
+
+        tree.extend(&children);
+        descendants.extend(children);
+    }
+
+    descendants
+}
+
+// Kills the processes with the given PIDs, if they are still running
+fn kill_processes(pids: &[Pid]) {
+    let mut sys = System::new();
+    sys.refresh_processes();
+
+    for pid in pids {
+        if let Some(process) = sys.process(*pid) {
+            process.kill();
+        }
+    }
+}
+
+// Waits for the proxy to accept connections on the given port.
+//
+// The proxy's output is consumed until then: no flow goes through the proxy
+// before the browser is launched, so it only holds messages of the proxy. If
+// it exits, or isn't ready before the timeout, the returned reason includes
+// the last lines it wrote.
+pub async fn wait_until_ready(
+    events: &mut Receiver<CommandEvent>,
+    port: u16,
+    timeout: Duration,
+) -> Result<(), String> {
+    let deadline = Instant::now() + timeout;
+    let mut output: Vec<String> = vec![];
+
+    loop {
+        if TcpStream::connect(("127.0.0.1", port)).await.is_ok() {
+            return Ok(());
+        }
+
+        if Instant::now() >= deadline {
+            return Err(failure_reason(
+                &format!(
+                    "the recorder proxy didn't start listening on port {} within {}s",
+                    port,
+                    timeout.as_secs()
+                ),
+                &output,
+            ));
+        }
+
+        tokio::select! {
+            event = events.recv() => match event {
+                Some(CommandEvent::Stdout(line)) | Some(CommandEvent::Stderr(line)) => {
+                    output.push(line.trim_end().to_string());
+                    if output.len() > OUTPUT_TAIL_LINES {
+                        output.remove(0);
+                    }
+                }
+                Some(CommandEvent::Error(e)) => output.push(e),
+                Some(CommandEvent::Terminated(payload)) => {
+                    let exit = match payload.code {
+                        Some(code) => format!("with code {}", code),
+                        None => "unexpectedly".to_string(),
+                    };
+                    return Err(failure_reason(
+                        &format!("the recorder proxy exited {} during startup", exit),
+                        &output,
+                    ));
+                }
+                None => {
+                    return Err(failure_reason(
+                        "the recorder proxy exited during startup",
+                        &output,
+                    ));
+                }
+                Some(_) => {}
+            },
+            _ = time::sleep(READINESS_PROBE_INTERVAL) => {}
+        }
+    }
+}
+
+// Waits for the given port to be free, which takes a moment after a
+// proxy listening on it has been killed.
+//
+// Returns false if the port is still in use once the timeout expired.
+pub async fn wait_until_port_free(port: u16, timeout: Duration) -> bool {
+    let deadline = Instant::now() + timeout;
+
+    loop {
+        if std::net::TcpListener::bind(("127.0.0.1", port)).is_ok() {
+            return true;
+        }
+
+        if Instant::now() >= deadline {
+            return false;
+        }
+
+        time::sleep(READINESS_PROBE_INTERVAL).await;
+    }
+}
+
+fn failure_reason(message: &str, output: &[String]) -> String {
+    if output.is_empty() {
+        return message.to_string();
+    }
+
+    format!("{}:\n{}", message, output.join("\n"))
+}
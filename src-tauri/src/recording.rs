@@ -0,0 +1,776 @@
+use std::collections::{BTreeMap, HashSet};
+
+use chrono::{DateTime, Utc};
+use serde_json::{Map, Value};
+
+use crate::models::{
+    RecordedFlow, RecordingFilter, RecordingMarker, RecordingSession, ScriptOptions,
+};
+
+// The shortest pause between two pages that is replayed as think time, in seconds
+const MIN_THINK_TIME: f64 = 1.0;
+
+// Headers that k6 computes on its own, or whose recorded value would be
+// wrong when replayed. Cookies are left to k6's cookie jar.
+const IGNORED_HEADERS: [&str; 6] = [
+    "host",
+    "content-length",
+    "connection",
+    "cookie",
+    "keep-alive",
+    "transfer-encoding",
+];
+
+// The file extensions of static assets
+const STATIC_ASSET_EXTENSIONS: [&str; 18] = [
+    "css", "js", "mjs", "map", "png", "jpg", "jpeg", "gif", "svg", "ico", "webp", "avif", "woff",
+    "woff2", "ttf", "otf", "eot", "mp4",
+];
+
+// The content types of static assets
+const STATIC_ASSET_CONTENT_TYPES: [&str; 7] = [
+    "image/",
+    "font/",
+    "audio/",
+    "video/",
+    "text/css",
+    "text/javascript",
+    "application/javascript",
+];
+
+// Recording holds the flows captured during a recorder session,
+// in the order in which they were first seen.
+pub struct Recording {
+    // The id of the session, derived from the time it started
+    id: String,
+
+    started_at: DateTime<Utc>,
+
+    // The project the session is saved to, if any
+    project_name: Option<String>,
+
+    // The filter deciding which flows are recorded
+    filter: RecordingFilter,
+
+    flows: Vec<RecordedFlow>,
+
+    // The markers added during the session, in the order they were added
+    markers: Vec<RecordingMarker>,
+
+    // Whether flows are being dropped instead of recorded
+    paused: bool,
+
+    // The ids of the flows dropped while paused, whose later
+    // updates must be dropped as well
+    paused_flow_ids: HashSet<String>,
+}
+
+impl Recording {
+    pub fn new() -> Self {
+        let started_at = Utc::now();
+
+        Self {
+            id: session_id(&started_at),
+            started_at,
+            project_name: None,
+            filter: RecordingFilter::default(),
+            flows: vec![],
+            markers: vec![],
+            paused: false,
+            paused_flow_ids: HashSet::new(),
+        }
+    }
+
+    // Starts a new session, discarding the flows of the previous one.
+    pub fn start(&mut self, project_name: Option<String>, filter: RecordingFilter) {
+        *self = Self::new();
+        self.project_name = project_name;
+        self.filter = filter;
+    }
+
+    // Resumes a session saved to the given project, discarding the state
+    // of the current one: a resumed session is neither paused nor filtered.
+    pub fn resume(&mut self, project_name: &str, session: RecordingSession) {
+        *self = Self::new();
+        self.id = session.id;
+        self.started_at = session.started_at;
+        self.project_name = Some(project_name.to_string());
+        self.flows = session.flows;
+        self.markers = session.markers;
+    }
+
+    // Stops recording flows until `unpause` is called.
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn unpause(&mut self) {
+        self.paused = false;
+    }
+
+    // Adds a marker with the given label, starting a new group of
+    // requests from now on.
+    pub fn add_marker(&mut self, label: &str) -> RecordingMarker {
+        let marker = RecordingMarker {
+            label: label.to_string(),
+            timestamp: Utc::now().timestamp_millis() as f64 / 1000.0,
+        };
+        self.markers.push(marker.clone());
+
+        marker
+    }
+
+    // Records a flow, if it passes the session's filter.
+    //
+    // The recorder reports a flow once when its request is sent, and once
+    // more when its response is received: the latter replaces the former.
+    // As the content type of a flow is only known once it received its
+    // response, a flow recorded at first can be dropped afterwards.
+    //
+    // Flows first seen while paused are dropped, along with their updates.
+    pub fn record(&mut self, flow: RecordedFlow) -> RecordOutcome {
+        let position = self.flows.iter().position(|f| f.id == flow.id);
+
+        if position.is_none() && (self.paused || self.paused_flow_ids.contains(&flow.id)) {
+            self.paused_flow_ids.insert(flow.id);
+            return RecordOutcome::Paused;
+        }
+
+        if !is_recorded(&self.filter, &flow) {
+            return match position {
+                Some(position) => {
+                    self.flows.remove(position);
+                    RecordOutcome::Dropped
+                }
+                None => RecordOutcome::Filtered,
+            };
+        }
+
+        match position {
+            Some(position) => self.flows[position] = flow,
+            None => self.flows.push(flow),
+        }
+
+        RecordOutcome::Recorded
+    }
+
+    pub fn flows(&self) -> &[RecordedFlow] {
+        &self.flows
+    }
+
+    pub fn markers(&self) -> &[RecordingMarker] {
+        &self.markers
+    }
+
+    pub fn project_name(&self) -> Option<&str> {
+        self.project_name.as_deref()
+    }
+
+    // Returns the session as it is persisted
+    pub fn to_session(&self) -> RecordingSession {
+        RecordingSession {
+            id: self.id.clone(),
+            started_at: self.started_at,
+            flows: self.flows.clone(),
+            markers: self.markers.clone(),
+        }
+    }
+}
+
+// The outcome of recording a flow
+#[derive(Debug, PartialEq)]
+pub enum RecordOutcome {
+    // The flow was recorded
+    Recorded,
+
+    // The flow was filtered out
+    Filtered,
+
+    // The flow was filtered out, and the previously recorded
+    // version of it has been dropped
+    Dropped,
+
+    // The flow was dropped as the recording is paused
+    Paused,
+}
+
+// Returns true if the flow passes the filter
+fn is_recorded(filter: &RecordingFilter, flow: &RecordedFlow) -> bool {
+    let host = &flow.request.host;
+
+    if !filter.allow_hosts.is_empty()
+        && !filter
+            .allow_hosts
+            .iter()
+            .any(|pattern| glob_matches(pattern, host))
+    {
+        return false;
+    }
+
+    if filter
+        .deny_hosts
+        .iter()
+        .any(|pattern| glob_matches(pattern, host))
+    {
+        return false;
+    }
+
+    if !filter.methods.is_empty()
+        && !filter
+            .methods
+            .iter()
+            .any(|method| method.eq_ignore_ascii_case(&flow.request.method))
+    {
+        return false;
+    }
+
+    let content_type = flow
+        .response
+        .as_ref()
+        .and_then(|response| header_value(&response.headers, "content-type"))
+        .map(|content_type| content_type.to_lowercase());
+    if let Some(content_type) = content_type {
+        if filter
+            .deny_content_types
+            .iter()
+            .any(|denied| content_type.starts_with(&denied.to_lowercase()))
+        {
+            return false;
+        }
+    }
+
+    true
+}
+
+// Returns true if the value matches the glob pattern, in which `*` matches
+// any sequence of characters. The comparison is case insensitive.
+fn glob_matches(pattern: &str, value: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let value: Vec<char> = value.to_lowercase().chars().collect();
+
+    let (mut p, mut v) = (0, 0);
+    // the position of the last `*` in the pattern, and of the value when we met it
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while v < value.len() {
+        if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, v));
+            p += 1;
+        } else if p < pattern.len() && pattern[p] == value[v] {
+            p += 1;
+            v += 1;
+        } else if let Some((star, matched)) = backtrack {
+            // let the last `*` match one more character
+            p = star + 1;
+            v = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+// Returns a new session holding flows which weren't captured by the
+// recorder, such as the ones imported from an HTTP Archive.
+pub fn imported_session(flows: Vec<RecordedFlow>) -> RecordingSession {
+    let started_at = Utc::now();
+
+    RecordingSession {
+        id: session_id(&started_at),
+        started_at,
+        flows,
+        markers: vec![],
+    }
+}
+
+// Returns the id of a session started at the given time.
+//
+// Ids are used as file names, so they are kept to alphanumeric
+// characters and dashes.
+fn session_id(started_at: &DateTime<Utc>) -> String {
+    started_at.format("%Y%m%d-%H%M%S-%3f").to_string()
+}
+
+// Generates a k6 script replaying the recorded flows.
+//
+// Requests are grouped by the page navigation they belong to, each page
+// becoming a `group()`, and the pauses the user made between pages are
+// replayed as think time. Once a marker has been added, requests are
+// grouped by marker instead, each group named after its marker's label.
+// Headers shared by all requests are declared once at the top of the script.
+pub fn to_k6_script(
+    flows: &[RecordedFlow],
+    markers: &[RecordingMarker],
+    options: &ScriptOptions,
+) -> String {
+    let flows: Vec<&RecordedFlow> = flows
+        .iter()
+        .filter(|flow| !(options.filter_static_assets && is_static_asset(flow)))
+        .collect();
+
+    let common_headers = common_headers(&flows);
+
+    let mut script = String::new();
+    script.push_str("import { group, sleep } from \"k6\";\n");
+    script.push_str("import http from \"k6/http\";\n\n");
+    script.push_str(&format!(
+        "const headers = {};\n\n",
+        Value::Object(to_js_object(&common_headers))
+    ));
+    script.push_str("export default function () {\n");
+
+    for (index, page) in pages(&flows, markers).iter().enumerate() {
+        if index > 0 {
+            script.push('\n');
+        }
+
+        if options.think_time {
+            if let Some(think_time) = page.think_time.filter(|t| *t >= MIN_THINK_TIME) {
+                script.push_str(&format!("  sleep({:.1});\n\n", think_time));
+            }
+        }
+
+        script.push_str(&format!(
+            "  group({}, function () {{\n",
+            js_string(&page.name)
+        ));
+        for flow in &page.flows {
+            if flow.request.is_content_redacted() {
+                script.push_str("    // body not captured by the recorder\n");
+            }
+            script.push_str(&format!("    {}\n", to_k6_request(flow, &common_headers)));
+        }
+        script.push_str("  });\n");
+    }
+
+    script.push_str("}\n");
+    script
+}
+
+// A page groups the requests triggered by a page navigation, or
+// following a marker
+struct Page<'a> {
+    // The name of the page, after the URL it navigated to or the
+    // label of its marker
+    name: String,
+
+    // Whether the page was started by a marker, in which case it
+    // isn't split by navigations
+    marked: bool,
+
+    flows: Vec<&'a RecordedFlow>,
+
+    // The time in seconds between the end of the previous page and the
+    // start of this one, if any
+    think_time: Option<f64>,
+}
+
+// Splits the flows into pages, starting a new page on every navigation
+// until the first marker, and on every marker from then on.
+//
+// Markers without any flow following them are left out.
+fn pages<'a>(flows: &[&'a RecordedFlow], markers: &[RecordingMarker]) -> Vec<Page<'a>> {
+    let mut pages: Vec<Page> = vec![];
+    let mut markers = markers.iter().peekable();
+
+    for flow in flows {
+        let mut marker = None;
+        while let Some(next) = markers.next_if(|m| m.timestamp <= flow.request.timestamp_start) {
+            marker = Some(next);
+        }
+
+        match (pages.last_mut(), marker) {
+            (_, Some(marker)) => pages.push(Page {
+                name: marker.label.clone(),
+                marked: true,
+                flows: vec![flow],
+                think_time: None,
+            }),
+            (Some(page), None) if page.marked || !is_navigation(flow) => page.flows.push(flow),
+            _ => pages.push(Page {
+                name: flow.request.url(),
+                marked: false,
+                flows: vec![flow],
+                think_time: None,
+            }),
+        }
+    }
+
+    for index in 1..pages.len() {
+        let think_time =
+            pages[index].flows[0].request.timestamp_start - page_end(&pages[index - 1]);
+        if think_time > 0.0 {
+            pages[index].think_time = Some(think_time);
+        }
+    }
+
+    pages
+}
+
+// Returns the unix timestamp, in seconds, at which the last flow of the page ended
+fn page_end(page: &Page) -> f64 {
+    page.flows
+        .iter()
+        .map(|flow| {
+            flow.response
+                .as_ref()
+                .and_then(|response| response.timestamp_end)
+                .or(flow.request.timestamp_end)
+                .unwrap_or(flow.request.timestamp_start)
+        })
+        .fold(f64::MIN, f64::max)
+}
+
+// Returns true if the flow is the navigation of the browser to a new page
+fn is_navigation(flow: &RecordedFlow) -> bool {
+    let headers = &flow.request.headers;
+    if let Some(mode) = header_value(headers, "sec-fetch-mode") {
+        return mode == "navigate";
+    }
+
+    // browsers not sending fetch metadata, and imported flows, are
+    // recognized by their HTML responses
+    flow.request.method == "GET"
+        && flow
+            .response
+            .as_ref()
+            .and_then(|response| header_value(&response.headers, "content-type"))
+            .is_some_and(|content_type| content_type.starts_with("text/html"))
+}
+
+// Returns true if the flow fetches a static asset: a stylesheet, a script, an image, a font...
+fn is_static_asset(flow: &RecordedFlow) -> bool {
+    let path = flow.request.path.split('?').next().unwrap_or_default();
+    let extension = path
+        .rsplit_once('.')
+        .map(|(_, extension)| extension.to_lowercase());
+    if let Some(extension) = extension {
+        if STATIC_ASSET_EXTENSIONS.contains(&extension.as_str()) {
+            return true;
+        }
+    }
+
+    flow.response
+        .as_ref()
+        .and_then(|response| header_value(&response.headers, "content-type"))
+        .is_some_and(|content_type| {
+            STATIC_ASSET_CONTENT_TYPES
+                .iter()
+                .any(|static_type| content_type.starts_with(static_type))
+        })
+}
+
+// Returns the headers of the flow that should be replayed, with lowercase names
+fn replayed_headers(flow: &RecordedFlow) -> BTreeMap<String, String> {
+    flow.request
+        .headers
+        .iter()
+        .map(|(name, value)| (name.to_lowercase(), value.clone()))
+        // HTTP/2 pseudo-headers are derived from the request itself
+        .filter(|(name, _)| !name.starts_with(':'))
+        .filter(|(name, _)| !IGNORED_HEADERS.contains(&name.as_str()))
+        .collect()
+}
+
+// Returns the headers sent with the same value by every flow
+fn common_headers(flows: &[&RecordedFlow]) -> BTreeMap<String, String> {
+    let mut flows = flows.iter();
+    let mut common = match flows.next() {
+        Some(flow) => replayed_headers(flow),
+        None => return BTreeMap::new(),
+    };
+
+    for flow in flows {
+        let headers = replayed_headers(flow);
+        common.retain(|name, value| headers.get(name) == Some(value));
+    }
+
+    common
+}
+
+// Returns the k6 statement replaying the flow's request
+fn to_k6_request(flow: &RecordedFlow, common_headers: &BTreeMap<String, String>) -> String {
+    let request = &flow.request;
+
+    let own_headers: BTreeMap<String, String> = replayed_headers(flow)
+        .into_iter()
+        .filter(|(name, _)| !common_headers.contains_key(name))
+        .collect();
+
+    let headers = if own_headers.is_empty() {
+        "headers".to_string()
+    } else {
+        format!(
+            "Object.assign({{}}, headers, {})",
+            Value::Object(to_js_object(&own_headers))
+        )
+    };
+
+    let body = if request.content.is_empty() || request.is_content_redacted() {
+        "null".to_string()
+    } else {
+        js_string(&request.content)
+    };
+
+    format!(
+        "http.request({}, {}, {}, {{ headers: {} }});",
+        js_string(&request.method),
+        js_string(&request.url()),
+        body,
+        headers,
+    )
+}
+
+// Returns the value of the first header with the given name, if any
+fn header_value<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(header_name, _)| header_name.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.as_str())
+}
+
+fn to_js_object(values: &BTreeMap<String, String>) -> Map<String, Value> {
+    values
+        .iter()
+        .map(|(name, value)| (name.clone(), Value::from(value.as_str())))
+        .collect()
+}
+
+// Returns the given value as a javascript string literal
+fn js_string(value: &str) -> String {
+    Value::from(value).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{RecordedRequest, RecordedResponse, REDACTED_CONTENT};
+
+    // Returns a GET flow of the given path, started at the given time and
+    // lasting 100ms, whose response has the given content type
+    fn flow(path: &str, content_type: &str, timestamp: f64) -> RecordedFlow {
+        RecordedFlow {
+            id: format!("{}-{}", path, timestamp),
+            timestamp_created: timestamp,
+            request: RecordedRequest {
+                method: "GET".to_string(),
+                scheme: "https".to_string(),
+                host: "example.com".to_string(),
+                port: 443,
+                path: path.to_string(),
+                http_version: "HTTP/1.1".to_string(),
+                headers: vec![("Accept".to_string(), "*/*".to_string())],
+                content: String::new(),
+                content_length: None,
+                timestamp_start: timestamp,
+                timestamp_end: Some(timestamp),
+            },
+            response: Some(RecordedResponse {
+                http_version: "HTTP/1.1".to_string(),
+                status_code: 200,
+                reason: "OK".to_string(),
+                headers: vec![("Content-Type".to_string(), content_type.to_string())],
+                content: String::new(),
+                content_length: None,
+                timestamp_start: Some(timestamp + 0.05),
+                timestamp_end: Some(timestamp + 0.1),
+            }),
+        }
+    }
+
+    fn marker(label: &str, timestamp: f64) -> RecordingMarker {
+        RecordingMarker {
+            label: label.to_string(),
+            timestamp,
+        }
+    }
+
+    #[test]
+    fn globs_match_any_sequence_of_characters() {
+        assert!(glob_matches("example.com", "example.com"));
+        assert!(glob_matches("*.example.com", "api.example.com"));
+        assert!(glob_matches("*.example.com", "a.b.example.com"));
+        assert!(!glob_matches("*.example.com", "example.com"));
+        assert!(glob_matches("api.*.com", "api.example.com"));
+        assert!(glob_matches("*", ""));
+        assert!(glob_matches("**", "anything"));
+        assert!(!glob_matches("", "example.com"));
+        assert!(!glob_matches("example.com", "example.com.evil.org"));
+    }
+
+    #[test]
+    fn globs_backtrack_over_partial_matches() {
+        assert!(glob_matches("*ab", "aab"));
+        assert!(glob_matches("*a*b", "xaxxab"));
+        assert!(glob_matches("a*b*c", "abbbcbc"));
+        assert!(!glob_matches("a*b*c", "abbbcb"));
+    }
+
+    #[test]
+    fn globs_are_case_insensitive() {
+        assert!(glob_matches("*.Example.COM", "API.example.com"));
+    }
+
+    #[test]
+    fn flows_are_filtered_by_host_and_method() {
+        let filter = RecordingFilter {
+            allow_hosts: vec!["*.example.com".to_string()],
+            deny_hosts: vec!["ads.example.com".to_string()],
+            methods: vec!["get".to_string()],
+            ..RecordingFilter::default()
+        };
+
+        let mut api = flow("/", "text/html", 10.0);
+        api.request.host = "api.example.com".to_string();
+        assert!(is_recorded(&filter, &api));
+
+        let mut ads = api.clone();
+        ads.request.host = "ADS.example.com".to_string();
+        assert!(!is_recorded(&filter, &ads));
+
+        let mut post = api.clone();
+        post.request.method = "POST".to_string();
+        assert!(!is_recorded(&filter, &post));
+    }
+
+    #[test]
+    fn resumed_sessions_start_over_from_the_saved_state() {
+        let mut recording = Recording::new();
+        recording.start(
+            None,
+            RecordingFilter {
+                deny_hosts: vec!["example.com".to_string()],
+                ..RecordingFilter::default()
+            },
+        );
+        recording.pause();
+        recording.record(flow("/", "text/html", 10.0));
+
+        let session = RecordingSession {
+            id: "saved".to_string(),
+            started_at: Utc::now(),
+            flows: vec![],
+            markers: vec![],
+        };
+        recording.resume("default", session);
+
+        assert_eq!(
+            recording.record(flow("/", "text/html", 10.0)),
+            RecordOutcome::Recorded
+        );
+        assert_eq!(recording.project_name(), Some("default"));
+        assert_eq!(recording.to_session().id, "saved");
+    }
+
+    #[test]
+    fn requests_are_grouped_by_page() {
+        let flows = [
+            flow("/", "text/html", 10.0),
+            flow("/api/user", "application/json", 10.2),
+            flow("/products", "text/html", 15.0),
+            flow("/api/products", "application/json", 15.2),
+        ];
+
+        let script = to_k6_script(&flows, &[], &ScriptOptions::default());
+
+        assert_eq!(script.matches("group(").count(), 2);
+        let home = script.find(r#"group("https://example.com/""#).unwrap();
+        let products = script
+            .find(r#"group("https://example.com/products""#)
+            .unwrap();
+        let user = script.find("/api/user").unwrap();
+        assert!(home < user && user < products);
+
+        // the pause between the two pages is replayed
+        assert!(script.contains("sleep(4.7);"));
+    }
+
+    #[test]
+    fn markers_take_over_the_grouping() {
+        let flows = [
+            flow("/", "text/html", 10.0),
+            flow("/login", "text/html", 12.0),
+            flow("/api/session", "application/json", 12.2),
+            flow("/account", "text/html", 14.0),
+        ];
+        let markers = [marker("log in", 11.0), marker("unused", 20.0)];
+
+        let script = to_k6_script(&flows, &markers, &ScriptOptions::default());
+
+        assert_eq!(script.matches("group(").count(), 2);
+        assert!(script.contains(r#"group("log in""#));
+        assert!(!script.contains("unused"));
+        // navigations following a marker stay in its group
+        assert!(!script.contains(r#"group("https://example.com/account""#));
+    }
+
+    #[test]
+    fn think_time_can_be_left_out() {
+        let flows = [
+            flow("/", "text/html", 10.0),
+            flow("/products", "text/html", 15.0),
+        ];
+        let options = ScriptOptions {
+            think_time: false,
+            ..ScriptOptions::default()
+        };
+
+        let script = to_k6_script(&flows, &[], &options);
+
+        assert!(!script.contains("sleep("));
+    }
+
+    #[test]
+    fn static_assets_are_filtered_out() {
+        let flows = [
+            flow("/", "text/html", 10.0),
+            flow("/style.css?v=2", "text/css", 10.1),
+            flow("/logo", "image/png", 10.2),
+            flow("/fonts/Inter.WOFF2", "application/octet-stream", 10.3),
+            flow("/api/items", "application/json", 10.4),
+        ];
+
+        let script = to_k6_script(&flows, &[], &ScriptOptions::default());
+        assert_eq!(script.matches("http.request(").count(), 2);
+        assert!(script.contains("/api/items"));
+
+        let options = ScriptOptions {
+            filter_static_assets: false,
+            ..ScriptOptions::default()
+        };
+        let script = to_k6_script(&flows, &[], &options);
+        assert_eq!(script.matches("http.request(").count(), 5);
+    }
+
+    #[test]
+    fn common_headers_are_declared_once() {
+        let mut api = flow("/api/items", "application/json", 10.2);
+        api.request
+            .headers
+            .push(("Authorization".to_string(), "Bearer t0ken".to_string()));
+        let flows = [flow("/", "text/html", 10.0), api];
+
+        let script = to_k6_script(&flows, &[], &ScriptOptions::default());
+
+        assert!(script.contains(r#"const headers = {"accept":"*/*"};"#));
+        assert_eq!(script.matches("Bearer t0ken").count(), 1);
+        assert!(script.contains(r#"Object.assign({}, headers, {"authorization":"Bearer t0ken"})"#));
+    }
+
+    #[test]
+    fn bodies_not_captured_are_left_out() {
+        let mut upload = flow("/api/avatar", "application/json", 10.2);
+        upload.request.method = "PUT".to_string();
+        upload.request.content = REDACTED_CONTENT.to_string();
+        let flows = [flow("/", "text/html", 10.0), upload];
+
+        let script = to_k6_script(&flows, &[], &ScriptOptions::default());
+
+        assert!(!script.contains(REDACTED_CONTENT));
+        assert!(script.contains(
+            "    // body not captured by the recorder\n    http.request(\"PUT\", \"https://example.com/api/avatar\", null,"
+        ));
+    }
+}
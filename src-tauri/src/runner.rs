@@ -0,0 +1,319 @@
+use std::collections::BTreeMap;
+use std::io::{self, Read, Write};
+use std::process::{Command, Stdio};
+use std::time::Instant;
+
+use chrono::Utc;
+use tokio::task;
+
+use crate::executable;
+use crate::models::{
+    EnvironmentVariable, StepOptions, StepResult, StepStatus, Suite, SuiteMode, SuiteResult,
+};
+
+// The outcome of a local k6 run
+pub struct K6Run {
+    // The exit code of k6, if it exited on its own
+    pub exit_code: Option<i32>,
+
+    // What k6 wrote to its stdout
+    pub output: String,
+}
+
+// Runs a k6 script locally, and waits for k6 to exit.
+//
+// The variables are exposed to the script through `__ENV`. They are passed
+// as environment variables of the process rather than with `-e`, so that
+// secrets don't show up in its command line.
+pub fn run_k6(
+    script: &str,
+    variables: &BTreeMap<String, EnvironmentVariable>,
+    args: &[String],
+    web_dashboard: bool,
+) -> io::Result<K6Run> {
+    let mut child = Command::new(executable::get_executable_path())
+        .arg("run")
+        .args(args)
+        .arg("-")
+        .envs(
+            variables
+                .iter()
+                .map(|(name, variable)| (name, &variable.value)),
+        )
+        .env("K6_WEB_DASHBOARD", web_dashboard.to_string())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(script.as_bytes())?;
+    }
+
+    let mut output = String::new();
+    if let Some(mut stdout) = child.stdout.take() {
+        stdout.read_to_string(&mut output)?;
+    }
+
+    let status = child.wait()?;
+    Ok(K6Run {
+        exit_code: status.code(),
+        output,
+    })
+}
+
+// Returns the k6 flags overriding the load options of a test
+pub fn step_args(options: &StepOptions) -> Vec<String> {
+    let mut args = vec![];
+    if let Some(vus) = options.vus {
+        args.push(format!("--vus={}", vus));
+    }
+    if let Some(duration) = &options.duration {
+        args.push(format!("--duration={}", duration));
+    }
+    if let Some(iterations) = options.iterations {
+        args.push(format!("--iterations={}", iterations));
+    }
+
+    args
+}
+
+// A step of a suite, ready to be ran
+pub struct PreparedStep {
+    pub test_name: String,
+    pub script: String,
+    pub variables: BTreeMap<String, EnvironmentVariable>,
+    pub args: Vec<String>,
+}
+
+// Runs the steps of a suite, as prepared from its steps, in order.
+//
+// Steps which couldn't be prepared are reported as errors. In a sequential
+// suite, a failing step stops the suite unless it allows continuing on
+// failure, and the remaining steps are reported as skipped. `on_step` is
+// called with the result of every step, in the order of the suite.
+pub async fn run_suite(
+    suite: &Suite,
+    steps: Vec<Result<PreparedStep, String>>,
+    on_step: impl Fn(usize, &StepResult),
+) -> SuiteResult {
+    let started_at = Utc::now();
+    let mut results = vec![];
+
+    match suite.mode {
+        SuiteMode::Sequential => {
+            let mut stopped = false;
+            for (index, (step, prepared)) in suite.steps.iter().zip(steps).enumerate() {
+                let result = match prepared {
+                    _ if stopped => StepResult::skipped(&step.test_name),
+                    Ok(prepared) => run_step(prepared).await,
+                    Err(e) => StepResult::error(&step.test_name, &e),
+                };
+
+                if result.status != StepStatus::Passed && !step.options.continue_on_failure {
+                    stopped = true;
+                }
+
+                on_step(index, &result);
+                results.push(result);
+            }
+        }
+        SuiteMode::Parallel => {
+            let handles: Vec<_> = steps
+                .into_iter()
+                .map(|prepared| prepared.map(|prepared| task::spawn(run_step(prepared))))
+                .collect();
+
+            for (index, (step, handle)) in suite.steps.iter().zip(handles).enumerate() {
+                let result = match handle {
+                    Ok(handle) => handle
+                        .await
+                        .unwrap_or_else(|e| StepResult::error(&step.test_name, &e.to_string())),
+                    Err(e) => StepResult::error(&step.test_name, &e),
+                };
+
+                on_step(index, &result);
+                results.push(result);
+            }
+        }
+    }
+
+    SuiteResult::new(&suite.name, started_at, results)
+}
+
+async fn run_step(step: PreparedStep) -> StepResult {
+    let started = Instant::now();
+    let test_name = step.test_name.clone();
+
+    let run =
+        task::spawn_blocking(move || run_k6(&step.script, &step.variables, &step.args, false))
+            .await;
+
+    let run = match run {
+        Ok(Ok(run)) => run,
+        Ok(Err(e)) => return StepResult::error(&test_name, &format!("failed to run k6: {}", e)),
+        Err(e) => return StepResult::error(&test_name, &e.to_string()),
+    };
+
+    let status = match run.exit_code {
+        Some(0) => StepStatus::Passed,
+        _ => StepStatus::Failed,
+    };
+
+    StepResult {
+        test_name,
+        status,
+        exit_code: run.exit_code,
+        duration: started.elapsed().as_secs_f64(),
+        output: run.output,
+        error: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use super::*;
+    use crate::models::SuiteStep;
+
+    fn suite(mode: SuiteMode, steps: &[(&str, bool)]) -> Suite {
+        Suite {
+            name: "checkout".to_string(),
+            mode,
+            steps: steps
+                .iter()
+                .map(|(test_name, continue_on_failure)| SuiteStep {
+                    test_name: test_name.to_string(),
+                    options: StepOptions {
+                        continue_on_failure: *continue_on_failure,
+                        ..StepOptions::default()
+                    },
+                })
+                .collect(),
+        }
+    }
+
+    // Returns steps which couldn't be prepared, so that no k6 process is ran
+    fn unprepared(suite: &Suite) -> Vec<Result<PreparedStep, String>> {
+        suite
+            .steps
+            .iter()
+            .map(|step| Err(format!("{} is broken", step.test_name)))
+            .collect()
+    }
+
+    fn statuses(result: &SuiteResult) -> Vec<StepStatus> {
+        result.steps.iter().map(|step| step.status).collect()
+    }
+
+    #[tokio::test]
+    async fn failing_steps_stop_sequential_suites() {
+        let suite = suite(
+            SuiteMode::Sequential,
+            &[("login", false), ("browse", false), ("buy", false)],
+        );
+
+        let reported = RefCell::new(vec![]);
+        let result = run_suite(&suite, unprepared(&suite), |index, step| {
+            reported.borrow_mut().push((index, step.status))
+        })
+        .await;
+
+        assert_eq!(
+            statuses(&result),
+            vec![StepStatus::Error, StepStatus::Skipped, StepStatus::Skipped]
+        );
+        assert_eq!(result.steps[0].error.as_deref(), Some("login is broken"));
+        assert_eq!(result.steps[1].error, None);
+        assert_eq!(
+            reported.into_inner(),
+            vec![
+                (0, StepStatus::Error),
+                (1, StepStatus::Skipped),
+                (2, StepStatus::Skipped)
+            ]
+        );
+
+        assert!(!result.passed);
+        assert_eq!(result.passed_count, 0);
+        assert_eq!(result.failed_count, 1);
+        assert_eq!(result.skipped_count, 2);
+    }
+
+    #[tokio::test]
+    async fn steps_continuing_on_failure_let_the_next_ones_run() {
+        let suite = suite(
+            SuiteMode::Sequential,
+            &[("login", true), ("browse", false), ("buy", true)],
+        );
+
+        let result = run_suite(&suite, unprepared(&suite), |_, _| {}).await;
+
+        assert_eq!(
+            statuses(&result),
+            vec![StepStatus::Error, StepStatus::Error, StepStatus::Skipped]
+        );
+        assert_eq!(result.failed_count, 2);
+        assert_eq!(result.skipped_count, 1);
+    }
+
+    #[tokio::test]
+    async fn parallel_suites_run_every_step() {
+        let suite = suite(
+            SuiteMode::Parallel,
+            &[("login", false), ("browse", false), ("buy", false)],
+        );
+
+        let reported = RefCell::new(vec![]);
+        let result = run_suite(&suite, unprepared(&suite), |index, _| {
+            reported.borrow_mut().push(index)
+        })
+        .await;
+
+        assert_eq!(statuses(&result), vec![StepStatus::Error; 3]);
+        assert_eq!(reported.into_inner(), vec![0, 1, 2]);
+        assert_eq!(result.failed_count, 3);
+        assert_eq!(result.skipped_count, 0);
+    }
+
+    #[test]
+    fn suite_results_count_their_steps() {
+        let passed = StepResult {
+            status: StepStatus::Passed,
+            exit_code: Some(0),
+            ..StepResult::skipped("login")
+        };
+        let failed = StepResult {
+            status: StepStatus::Failed,
+            exit_code: Some(99),
+            ..StepResult::skipped("browse")
+        };
+
+        let result = SuiteResult::new("checkout", Utc::now(), vec![passed.clone()]);
+        assert!(result.passed);
+        assert_eq!(result.passed_count, 1);
+
+        let result = SuiteResult::new(
+            "checkout",
+            Utc::now(),
+            vec![passed, failed, StepResult::skipped("buy")],
+        );
+        assert!(!result.passed);
+        assert_eq!(result.passed_count, 1);
+        assert_eq!(result.failed_count, 1);
+        assert_eq!(result.skipped_count, 1);
+        assert!(result.finished_at >= result.started_at);
+    }
+
+    #[test]
+    fn step_options_override_the_load_options() {
+        let options = StepOptions {
+            vus: Some(10),
+            duration: Some("30s".to_string()),
+            ..StepOptions::default()
+        };
+
+        assert_eq!(step_args(&options), vec!["--vus=10", "--duration=30s"]);
+        assert!(step_args(&StepOptions::default()).is_empty());
+    }
+}
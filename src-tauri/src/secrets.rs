@@ -0,0 +1,417 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::{fs, io};
+
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+
+use crate::storage;
+
+// The service name secrets are stored under in the OS keyring
+const KEYRING_SERVICE: &str = "kroco6";
+// The entry used to check whether the OS keyring is usable
+const KEYRING_PROBE_KEY: &str = "kroco6-keyring-probe";
+
+const SECRETS_FILE: &str = "secrets.json";
+const SECRETS_KEY_FILE: &str = "secrets.key";
+
+// The file recording which backend holds the secrets
+const SECRET_BACKEND_FILE: &str = "secret_backend";
+
+// The size in bytes of the nonces used by AES-GCM
+const NONCE_SIZE: usize = 12;
+
+// SecretStore is the interface to the storage of sensitive values,
+// such as cloud tokens, which should never end up in plaintext
+// in our configuration files.
+//
+// Secrets are identified by a key, which is safe to store in
+// configuration files as a reference to the secret.
+pub trait SecretStore: Send + Sync {
+    // Get the secret stored under the given key.
+    //
+    // Returns None if no secret is stored under that key.
+    fn get(&self, key: &str) -> io::Result<Option<String>>;
+
+    // Store a secret under the given key, replacing any
+    // previously stored value.
+    fn set(&self, key: &str, secret: &str) -> io::Result<()>;
+
+    // Delete the secret stored under the given key.
+    //
+    // Deleting a secret that doesn't exist is not an error.
+    fn delete(&self, key: &str) -> io::Result<()>;
+}
+
+// Returns the secret store the application should use.
+//
+// The OS keyring is preferred whenever it is usable on first launch, and
+// we fall back to an encrypted file in the storage directory otherwise
+// (e.g. on Linux systems without a running secret service). The choice is
+// recorded, so that secrets are always looked up where they were stored:
+// fails if they are held by the keyring and it can't be reached.
+pub fn default_secret_store(storage_path: &Path) -> io::Result<Arc<dyn SecretStore>> {
+    let keyring = KeyringSecretStore::new();
+    match secret_backend(storage_path, || keyring.probe())? {
+        SecretBackend::Keyring => {
+            keyring.probe().map_err(|e| {
+                io::Error::new(
+                    e.kind(),
+                    format!("the OS keyring holding the secrets can't be reached: {}", e),
+                )
+            })?;
+            Ok(Arc::new(keyring))
+        }
+        SecretBackend::File => Ok(Arc::new(FileSecretStore::new(storage_path.to_path_buf()))),
+    }
+}
+
+// Where the secrets of the application are stored
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum SecretBackend {
+    Keyring,
+    File,
+}
+
+impl SecretBackend {
+    fn name(&self) -> &'static str {
+        match self {
+            SecretBackend::Keyring => "keyring",
+            SecretBackend::File => "file",
+        }
+    }
+}
+
+// Returns the backend recorded in the storage directory, choosing and
+// recording one if there is none yet.
+fn secret_backend(
+    storage_path: &Path,
+    probe_keyring: impl FnOnce() -> io::Result<()>,
+) -> io::Result<SecretBackend> {
+    let backend_path = storage_path.join(SECRET_BACKEND_FILE);
+    if backend_path.exists() {
+        return match fs::read_to_string(&backend_path)?.trim() {
+            "keyring" => Ok(SecretBackend::Keyring),
+            "file" => Ok(SecretBackend::File),
+            name => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unknown secret backend {}", name),
+            )),
+        };
+    }
+
+    // earlier versions chose on every launch, and only created the secrets
+    // file after falling back to it
+    let backend = if storage_path.join(SECRETS_FILE).exists() {
+        SecretBackend::File
+    } else {
+        match probe_keyring() {
+            Ok(()) => SecretBackend::Keyring,
+            Err(e) => {
+                eprintln!(
+                    "the OS keyring can't be reached, secrets are stored in an encrypted file instead: {}",
+                    e
+                );
+                SecretBackend::File
+            }
+        }
+    };
+
+    storage::write_atomic(&backend_path, backend.name())?;
+    Ok(backend)
+}
+
+// A SecretStore backed by the OS keyring: the Keychain on macOS,
+// the Credential Manager on Windows and the secret service on Linux.
+pub struct KeyringSecretStore;
+
+impl KeyringSecretStore {
+    pub fn new() -> Self {
+        Self
+    }
+
+    // Checks that the OS keyring can be reached.
+    pub fn probe(&self) -> io::Result<()> {
+        match self.entry(KEYRING_PROBE_KEY)?.get_password() {
+            Ok(_) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(e) => Err(keyring_error(e)),
+        }
+    }
+
+    fn entry(&self, key: &str) -> io::Result<keyring::Entry> {
+        keyring::Entry::new(KEYRING_SERVICE, key).map_err(keyring_error)
+    }
+}
+
+impl SecretStore for KeyringSecretStore {
+    fn get(&self, key: &str) -> io::Result<Option<String>> {
+        match self.entry(key)?.get_password() {
+            Ok(secret) => Ok(Some(secret)),
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(e) => Err(keyring_error(e)),
+        }
+    }
+
+    fn set(&self, key: &str, secret: &str) -> io::Result<()> {
+        self.entry(key)?.set_password(secret).map_err(keyring_error)
+    }
+
+    fn delete(&self, key: &str) -> io::Result<()> {
+        match self.entry(key)?.delete_password() {
+            Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(e) => Err(keyring_error(e)),
+        }
+    }
+}
+
+fn keyring_error(e: keyring::Error) -> io::Error {
+    io::Error::other(e.to_string())
+}
+
+// A SecretStore backed by an encrypted file in the application's
+// storage directory, used when the OS keyring is not available.
+//
+// Secrets are encrypted with AES-256-GCM using a key generated on first
+// use and stored next to the secrets file, readable only by the current user.
+// This keeps secrets out of the configuration files and protects them from
+// being accidentally shared, but not from someone who can read the key file.
+pub struct FileSecretStore {
+    file_path: PathBuf,
+    key_path: PathBuf,
+
+    // Serializes the read-modify-write cycles on the secrets file
+    lock: Mutex<()>,
+}
+
+impl FileSecretStore {
+    pub fn new(storage_path: PathBuf) -> Self {
+        Self {
+            file_path: storage_path.join(SECRETS_FILE),
+            key_path: storage_path.join(SECRETS_KEY_FILE),
+            lock: Mutex::new(()),
+        }
+    }
+
+    // Returns the cipher used to encrypt and decrypt secrets, generating
+    // its key if it doesn't exist yet.
+    fn cipher(&self) -> io::Result<Aes256Gcm> {
+        if !self.key_path.exists() {
+            let key = Aes256Gcm::generate_key(OsRng);
+            storage::write_atomic_private(&self.key_path, key)?;
+        }
+
+        let key = fs::read(&self.key_path)?;
+        if key.len() != 32 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "invalid secrets key",
+            ));
+        }
+
+        Ok(Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key)))
+    }
+
+    // Loads the encrypted secrets, as base64 encoded nonce and ciphertext pairs
+    fn load(&self) -> io::Result<BTreeMap<String, String>> {
+        if !self.file_path.exists() {
+            return Ok(BTreeMap::new());
+        }
+
+        let file = fs::File::open(&self.file_path)?;
+        let secrets = serde_json::from_reader(file)?;
+        Ok(secrets)
+    }
+
+    fn save(&self, secrets: &BTreeMap<String, String>) -> io::Result<()> {
+        storage::write_atomic_private(&self.file_path, serde_json::to_vec_pretty(secrets)?)
+    }
+}
+
+impl SecretStore for FileSecretStore {
+    fn get(&self, key: &str) -> io::Result<Option<String>> {
+        let _guard = self.lock.lock().unwrap();
+
+        let encoded = match self.load()?.remove(key) {
+            Some(encoded) => encoded,
+            None => return Ok(None),
+        };
+
+        let invalid_secret = || io::Error::new(io::ErrorKind::InvalidData, "invalid secret");
+
+        let sealed = BASE64.decode(encoded).map_err(|_| invalid_secret())?;
+        if sealed.len() < NONCE_SIZE {
+            return Err(invalid_secret());
+        }
+        let (nonce, ciphertext) = sealed.split_at(NONCE_SIZE);
+
+        let plaintext = self
+            .cipher()?
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| invalid_secret())?;
+
+        String::from_utf8(plaintext)
+            .map(Some)
+            .map_err(|_| invalid_secret())
+    }
+
+    fn set(&self, key: &str, secret: &str) -> io::Result<()> {
+        let _guard = self.lock.lock().unwrap();
+
+        let nonce = Aes256Gcm::generate_nonce(OsRng);
+        let ciphertext = self
+            .cipher()?
+            .encrypt(&nonce, secret.as_bytes())
+            .map_err(|_| io::Error::other("failed to encrypt secret"))?;
+
+        let mut sealed = nonce.to_vec();
+        sealed.extend(ciphertext);
+
+        let mut secrets = self.load()?;
+        secrets.insert(key.to_string(), BASE64.encode(sealed));
+        self.save(&secrets)
+    }
+
+    fn delete(&self, key: &str) -> io::Result<()> {
+        let _guard = self.lock.lock().unwrap();
+
+        let mut secrets = self.load()?;
+        if secrets.remove(key).is_some() {
+            self.save(&secrets)?;
+        }
+
+        Ok(())
+    }
+}
+
+// A SecretStore keeping secrets in memory only, which lets tests
+// check what ends up in the secret store.
+#[cfg(test)]
+pub struct MemorySecretStore {
+    secrets: Mutex<BTreeMap<String, String>>,
+}
+
+#[cfg(test)]
+impl MemorySecretStore {
+    pub fn new() -> Self {
+        Self {
+            secrets: Mutex::new(BTreeMap::new()),
+        }
+    }
+}
+
+#[cfg(test)]
+impl SecretStore for MemorySecretStore {
+    fn get(&self, key: &str) -> io::Result<Option<String>> {
+        Ok(self.secrets.lock().unwrap().get(key).cloned())
+    }
+
+    fn set(&self, key: &str, secret: &str) -> io::Result<()> {
+        self.secrets
+            .lock()
+            .unwrap()
+            .insert(key.to_string(), secret.to_string());
+        Ok(())
+    }
+
+    fn delete(&self, key: &str) -> io::Result<()> {
+        self.secrets.lock().unwrap().remove(key);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unavailable() -> io::Result<()> {
+        Err(io::Error::other("no secret service"))
+    }
+
+    #[test]
+    fn secrets_survive_a_round_trip_through_the_file_store() {
+        let storage = tempfile::tempdir().unwrap();
+        let secret_store = FileSecretStore::new(storage.path().to_path_buf());
+
+        assert_eq!(secret_store.get("token").unwrap(), None);
+        secret_store.set("token", "t0ken").unwrap();
+        secret_store.set("other", "0ther").unwrap();
+
+        let file = fs::read_to_string(storage.path().join(SECRETS_FILE)).unwrap();
+        assert!(!file.contains("t0ken"));
+
+        // a new store reads the secrets written by the previous one
+        let secret_store = FileSecretStore::new(storage.path().to_path_buf());
+        assert_eq!(secret_store.get("token").unwrap().as_deref(), Some("t0ken"));
+        assert_eq!(secret_store.get("other").unwrap().as_deref(), Some("0ther"));
+
+        secret_store.delete("token").unwrap();
+        secret_store.delete("token").unwrap();
+        assert_eq!(secret_store.get("token").unwrap(), None);
+        assert_eq!(secret_store.get("other").unwrap().as_deref(), Some("0ther"));
+    }
+
+    #[test]
+    fn tampered_secrets_are_rejected() {
+        let storage = tempfile::tempdir().unwrap();
+        let secret_store = FileSecretStore::new(storage.path().to_path_buf());
+        secret_store.set("token", "t0ken").unwrap();
+
+        let mut secrets = secret_store.load().unwrap();
+        let mut sealed = BASE64.decode(&secrets["token"]).unwrap();
+        let last = sealed.len() - 1;
+        sealed[last] ^= 1;
+        secrets.insert("token".to_string(), BASE64.encode(&sealed));
+        secrets.insert("short".to_string(), BASE64.encode([0; 4]));
+        secret_store.save(&secrets).unwrap();
+
+        let e = secret_store.get("token").unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+        let e = secret_store.get("short").unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn secrets_are_rejected_under_another_key() {
+        let storage = tempfile::tempdir().unwrap();
+        let secret_store = FileSecretStore::new(storage.path().to_path_buf());
+        secret_store.set("token", "t0ken").unwrap();
+
+        let key = Aes256Gcm::generate_key(OsRng);
+        storage::write_atomic_private(&storage.path().join(SECRETS_KEY_FILE), key).unwrap();
+
+        let e = secret_store.get("token").unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn the_secret_backend_is_chosen_once() {
+        let storage = tempfile::tempdir().unwrap();
+
+        let backend = secret_backend(storage.path(), unavailable).unwrap();
+        assert_eq!(backend, SecretBackend::File);
+
+        // the keyring becoming available doesn't hide the secrets stored in the file
+        let backend = secret_backend(storage.path(), || Ok(())).unwrap();
+        assert_eq!(backend, SecretBackend::File);
+
+        let storage = tempfile::tempdir().unwrap();
+        let backend = secret_backend(storage.path(), || Ok(())).unwrap();
+        assert_eq!(backend, SecretBackend::Keyring);
+        let backend = secret_backend(storage.path(), unavailable).unwrap();
+        assert_eq!(backend, SecretBackend::Keyring);
+    }
+
+    #[test]
+    fn secrets_files_of_earlier_versions_keep_the_file_backend() {
+        let storage = tempfile::tempdir().unwrap();
+        FileSecretStore::new(storage.path().to_path_buf())
+            .set("token", "t0ken")
+            .unwrap();
+
+        let backend = secret_backend(storage.path(), || Ok(())).unwrap();
+        assert_eq!(backend, SecretBackend::File);
+    }
+}
@@ -0,0 +1,228 @@
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use fs2::FileExt;
+
+// The file locked by the running instance of the app in its storage directory
+const LOCK_FILE: &str = "kroco6.lock";
+
+// Distinguishes the temporary files of concurrent writes of the same file
+static TEMPORARY_FILES: AtomicU64 = AtomicU64::new(0);
+
+// Writes the contents to the file at the given path, atomically.
+//
+// The contents are written to a temporary file next to the target one,
+// flushed to disk, and then renamed over the target. A crash mid-write
+// leaves either the previous file or the new one, never a truncated one.
+pub fn write_atomic(path: &Path, contents: impl AsRef<[u8]>) -> io::Result<()> {
+    write_atomic_with(path, contents.as_ref(), false)
+}
+
+// Writes the contents to the file at the given path atomically, like
+// write_atomic, restricting the file's permissions to the current user.
+pub fn write_atomic_private(path: &Path, contents: impl AsRef<[u8]>) -> io::Result<()> {
+    write_atomic_with(path, contents.as_ref(), true)
+}
+
+// Writes the value as pretty-printed JSON to the file at the given path, atomically.
+pub fn write_json_atomic<T: serde::Serialize>(path: &Path, value: &T) -> io::Result<()> {
+    write_atomic(path, serde_json::to_vec_pretty(value)?)
+}
+
+// Writes the contents to the file at the given path atomically, the
+// temporary file being created only readable by the current user when
+// the file is private, so that its contents are never exposed.
+fn write_atomic_with(path: &Path, contents: &[u8], private: bool) -> io::Result<()> {
+    let temporary_path = temporary_path(path)?;
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    if private {
+        restrict_permissions(&mut options);
+    }
+
+    let result = options
+        .open(&temporary_path)
+        .and_then(|mut file| {
+            file.write_all(contents)?;
+            file.sync_all()
+        })
+        .and_then(|_| fs::rename(&temporary_path, path));
+
+    if result.is_err() {
+        let _ = fs::remove_file(&temporary_path);
+    }
+    result?;
+
+    sync_directory(path)
+}
+
+// Returns the path of the temporary file the file at the given path is
+// written to before being renamed. It is hidden, so that an interrupted
+// write isn't mistaken for a stored file, and unique to the write, so that
+// concurrent writes of the same file don't write to the same temporary file.
+fn temporary_path(path: &Path) -> io::Result<PathBuf> {
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "invalid file path"))?;
+
+    Ok(path.with_file_name(format!(
+        ".{}.{}.{}.tmp",
+        file_name.to_string_lossy(),
+        std::process::id(),
+        TEMPORARY_FILES.fetch_add(1, Ordering::Relaxed)
+    )))
+}
+
+// Flushes the directory holding the file at the given path, so that
+// the rename of the file is itself persisted.
+#[cfg(unix)]
+fn sync_directory(path: &Path) -> io::Result<()> {
+    match path.parent() {
+        Some(directory) if !directory.as_os_str().is_empty() => File::open(directory)?.sync_all(),
+        _ => Ok(()),
+    }
+}
+
+// Directories can't be opened as files on Windows, where renames are
+// persisted along with the renamed file.
+#[cfg(not(unix))]
+fn sync_directory(_path: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+// Restricts the permissions of the files created with the options to the current user.
+#[cfg(unix)]
+fn restrict_permissions(options: &mut fs::OpenOptions) {
+    use std::os::unix::fs::OpenOptionsExt;
+    options.mode(0o600);
+}
+
+// On Windows, files in the user's configuration directory are already
+// only accessible to that user.
+#[cfg(not(unix))]
+fn restrict_permissions(_options: &mut fs::OpenOptions) {}
+
+// StorageLock is an exclusive lock on the app's storage directory, which
+// prevents two instances of the app from changing the same files.
+//
+// The lock is released when dropped, or when the process exits.
+pub struct StorageLock {
+    _file: File,
+}
+
+impl StorageLock {
+    // Locks the storage directory, failing if another instance
+    // of the app already holds the lock.
+    pub fn acquire(storage_path: &Path) -> io::Result<Self> {
+        let path = storage_path.join(LOCK_FILE);
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&path)?;
+
+        file.try_lock_exclusive().map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::WouldBlock,
+                format!(
+                    "{} is locked by another instance of kroco6: {}",
+                    storage_path.display(),
+                    e
+                ),
+            )
+        })?;
+
+        Ok(Self { _file: file })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn atomic_writes_replace_the_file() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("file.json");
+
+        write_atomic(&path, "first").unwrap();
+        write_atomic(&path, "second").unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "second");
+        assert_eq!(fs::read_dir(directory.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn failed_writes_leave_no_temporary_file() {
+        let directory = tempfile::tempdir().unwrap();
+
+        // a file can't be renamed over a directory
+        let path = directory.path().join("directory");
+        fs::create_dir(&path).unwrap();
+        fs::write(path.join("file"), "").unwrap();
+        assert!(write_atomic(&path, "contents").is_err());
+
+        assert!(write_atomic(&directory.path().join("missing/file"), "contents").is_err());
+
+        let names: Vec<_> = fs::read_dir(directory.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(names, vec!["directory"]);
+    }
+
+    #[test]
+    fn concurrent_writes_of_a_file_install_one_of_them() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = Arc::new(directory.path().join("file"));
+
+        let contents: Vec<String> = (0..8).map(|index| index.to_string().repeat(4096)).collect();
+        let writers: Vec<_> = contents
+            .iter()
+            .cloned()
+            .map(|contents| {
+                let path = path.clone();
+                thread::spawn(move || write_atomic(&path, contents))
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap().unwrap();
+        }
+
+        let written = fs::read_to_string(path.as_ref()).unwrap();
+        assert!(contents.contains(&written));
+        assert_eq!(fs::read_dir(directory.path()).unwrap().count(), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn private_files_are_only_readable_by_the_current_user() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("secrets.key");
+        write_atomic(&path, "public").unwrap();
+        write_atomic_private(&path, "private").unwrap();
+
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "private");
+    }
+
+    #[test]
+    fn the_storage_is_locked_by_a_single_instance() {
+        let directory = tempfile::tempdir().unwrap();
+
+        let lock = StorageLock::acquire(directory.path()).unwrap();
+        let error = StorageLock::acquire(directory.path()).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::WouldBlock);
+
+        drop(lock);
+        assert!(StorageLock::acquire(directory.path()).is_ok());
+    }
+}
@@ -1,9 +1,19 @@
 import { invoke } from "@tauri-apps/api/tauri";
 
+// The value secret variables hold when loaded, in place of their actual value
+export const SECRET_MASK = "********";
+
+export interface EnvironmentVariable {
+  value: string;
+  // Secret values are kept out of the environments file, and are
+  // only injected into k6 runs
+  secret: boolean;
+}
+
 export interface Environment {
   name: string;
   description?: string;
-  variables: Record<string, string>;
+  variables: Record<string, EnvironmentVariable>;
 }
 
 export interface EnvironmentsData {
@@ -28,16 +38,216 @@ export async function saveEnvironments(environmentsData: EnvironmentsData): Prom
   return await invoke("save_environments", { environmentsData });
 }
 
+/**
+ * Create an empty environment
+ *
+ * @param name The name of the environment
+ * @param description The description of the environment
+ * @param projectName The project to create the environment in, a global one is created if not provided
+ * @returns The environments of the scope after the change
+ */
+export async function createEnvironment(
+  name: string,
+  description: string | null = null,
+  projectName: string | null = null,
+): Promise<EnvironmentsData> {
+  return await invoke("create_environment", { projectName, name, description });
+}
+
+/**
+ * Rename an environment
+ *
+ * @returns The environments of the scope after the change
+ */
+export async function renameEnvironment(
+  name: string,
+  newName: string,
+  projectName: string | null = null,
+): Promise<EnvironmentsData> {
+  return await invoke("rename_environment", { projectName, name, newName });
+}
+
+/**
+ * Delete an environment, the active global environment can't be deleted
+ *
+ * @returns The environments of the scope after the change
+ */
+export async function deleteEnvironment(
+  name: string,
+  projectName: string | null = null,
+): Promise<EnvironmentsData> {
+  return await invoke("delete_environment", { projectName, name });
+}
+
+/**
+ * Set a variable of an environment, creating it if needed
+ *
+ * Setting a secret variable to SECRET_MASK keeps its current value.
+ *
+ * @returns The environments of the scope after the change
+ */
+export async function setVariable(
+  environmentName: string,
+  name: string,
+  variable: EnvironmentVariable,
+  projectName: string | null = null,
+): Promise<EnvironmentsData> {
+  return await invoke("set_variable", {
+    projectName,
+    environmentName,
+    name,
+    value: variable.value,
+    secret: variable.secret,
+  });
+}
+
+/**
+ * Remove a variable from an environment
+ *
+ * @returns The environments of the scope after the change
+ */
+export async function unsetVariable(
+  environmentName: string,
+  name: string,
+  projectName: string | null = null,
+): Promise<EnvironmentsData> {
+  return await invoke("unset_variable", { projectName, environmentName, name });
+}
+
+/**
+ * Set the active environment, a project can clear its own with an empty name
+ *
+ * @returns The environments of the scope after the change
+ */
+export async function setActiveEnvironment(
+  name: string,
+  projectName: string | null = null,
+): Promise<EnvironmentsData> {
+  return await invoke("set_active_environment", { projectName, name });
+}
+
+export type EnvironmentFileFormat = "Dotenv" | "Json";
+
+/**
+ * Import an environment from a `.env` file, a JSON object of variables or a
+ * Postman environment export, named after the file unless a name is given
+ *
+ * @returns The environments of the scope after the import
+ */
+export async function importEnvironment(
+  path: string,
+  projectName: string | null = null,
+  environmentName: string | null = null,
+): Promise<EnvironmentsData> {
+  return await invoke("import_environment", { path, projectName, environmentName });
+}
+
+/**
+ * Export the variables of an environment to a `.env` or JSON file, the format
+ * being inferred from the extension when not given. Secrets are left out
+ * unless included explicitly.
+ */
+export async function exportEnvironment(
+  path: string,
+  environmentName: string,
+  projectName: string | null = null,
+  format: EnvironmentFileFormat | null = null,
+  includeSecrets = false,
+): Promise<void> {
+  return await invoke("export_environment", {
+    path,
+    projectName,
+    environmentName,
+    format,
+    includeSecrets,
+  });
+}
+
+/**
+ * Load the environments defined by a project, which take precedence over the global ones
+ *
+ * @param projectName The name of the project
+ */
+export async function loadProjectEnvironments(projectName: string): Promise<EnvironmentsData> {
+  return await invoke("load_project_environments", { projectName });
+}
+
+/**
+ * Save the environments defined by a project
+ *
+ * @param projectName The name of the project
+ * @param environmentsData The environments of the project
+ */
+export async function saveProjectEnvironments(
+  projectName: string,
+  environmentsData: EnvironmentsData,
+): Promise<void> {
+  return await invoke("save_project_environments", { projectName, environmentsData });
+}
+
+/**
+ * Load the environments in effect for a project: the global ones, overridden by the project's
+ *
+ * @param projectName The name of the project
+ */
+export async function loadEffectiveEnvironments(projectName: string): Promise<EnvironmentsData> {
+  return await invoke("load_effective_environments", { projectName });
+}
+
+export type EnvironmentScope = "Global" | "Project";
+
+export interface ResolvedVariable {
+  value: string;
+  secret: boolean;
+  // The scope the variable comes from
+  scope: EnvironmentScope;
+}
+
+/**
+ * Get the variables in effect for a project, secret values are masked
+ *
+ * @param projectName The name of the project
+ * @param environmentName The environment to resolve, the active one if not provided
+ */
+export async function getEffectiveVariables(
+  projectName: string,
+  environmentName: string | null = null,
+): Promise<Record<string, ResolvedVariable>> {
+  return await invoke("get_effective_variables", { projectName, environmentName });
+}
+
+/**
+ * Get the environment in effect for a project, with the references its values hold resolved
+ *
+ * Secrets, and the variables derived from them or from the app's environment, are
+ * masked and flagged as secret: their value is only known to k6 runs.
+ *
+ * @param projectName The name of the project
+ * @param environmentName The environment to resolve, the active one if not provided
+ */
+export async function getInterpolatedEnvironment(
+  projectName: string,
+  environmentName: string | null = null,
+): Promise<Environment> {
+  return await invoke("get_interpolated_environment", { projectName, environmentName });
+}
+
 export interface ProjectConfig {
   cloud_token: string;
+  // The key the cloud token is stored under in the secret store
+  cloud_token_ref?: string;
   cloud_project_id: string;
 }
 
 export interface Project {
   name: string;
-  test_collections?: Array<Test>;
+  test_collections?: Array<TestCollection>;
   description?: string;
   project_config?: ProjectConfig;
+  test_count?: number;
+  suite_count?: number;
+  // When a file of the project was last changed
+  last_modified?: string | null;
 }
 
 export class Test {
@@ -51,6 +261,10 @@ export class Test {
   // The content of the test serialized as a string.
   content: string;
 
+  // The description, tags and owner of the test. When creating a test,
+  // only these are taken into account.
+  metadata?: TestMetadata;
+
   constructor(name: string, kind: TestKind, content: string) {
     this.name = name;
     this.kind = kind;
@@ -58,6 +272,38 @@ export class Test {
   }
 }
 
+// A test of a project, without its content
+export interface TestSummary {
+  name: string;
+  kind: TestKind;
+  last_modified: string | null;
+  tags: string[];
+}
+
+// The metadata kept alongside a test
+export interface TestMetadata {
+  description: string;
+  // Sorted and without duplicates
+  tags: string[];
+  owner: string | null;
+  created_at: string;
+  updated_at: string;
+  // The status of the last run of the test, locally or as part of a suite
+  last_run_status: StepStatus | null;
+  last_run_at: string | null;
+}
+
+// A change to the metadata of a test, fields left out are kept as is
+export interface TestMetadataUpdate {
+  description?: string;
+  tags?: string[];
+  // An empty owner clears it
+  owner?: string;
+}
+
+// A single test, or a suite of tests
+export type TestCollection = { Test: TestSummary } | { Suite: Suite };
+
 // TestKind indicates the underlying type of a test's
 // content: serialized blocks, javascript or an
 // openapi specification.
@@ -66,12 +312,26 @@ export type TestKind = "Blocks" | "Javascript" | "OpenAPI";
 /**
  * List all projects
  *
+ * The listed projects only hold their description and the number of their
+ * tests and suites, see getProject for their content and configuration.
+ *
  * @returns A list of all projects
  */
 export async function listProjects(): Promise<Project[]> {
   return await invoke("list_projects", {});
 }
 
+/**
+ * Get a project, along with its configuration and the summaries
+ * of its tests and suites
+ *
+ * @param name The name of the project
+ * @returns The project
+ */
+export async function getProject(name: string): Promise<Project> {
+  return await invoke("get_project", { name });
+}
+
 /**
  * Create a new project
  *
@@ -101,10 +361,11 @@ export async function createTest(projectName: string, test: Test): Promise<Test>
  * List the tests for a project
  *
  * @param projectName The name of the parent project to list tests for
- * @returns The list of tests for the project
+ * @param tag An optional tag, case insensitive, the listed tests must be labelled with
+ * @returns The list of tests for the project, along with their metadata
  */
-export async function listTests(projectName: string): Promise<Test[]> {
-  return await invoke("list_tests", { projectName });
+export async function listTests(projectName: string, tag: string | null = null): Promise<Test[]> {
+  return await invoke("list_tests", { projectName, tag });
 }
 
 /**
@@ -144,18 +405,98 @@ export async function saveTest(
   return await invoke("save_test", { projectName, testName, newContent });
 }
 
-export function runScriptLocally(script: string): Promise<string> {
-  return invoke("open_run_window", { script });
+/**
+ * Get the metadata of a test
+ *
+ * @param projectName The name of the parent project
+ * @param testName The name of the test
+ * @returns The metadata of the test
+ */
+export async function getTestMetadata(
+  projectName: string,
+  testName: string,
+): Promise<TestMetadata> {
+  return await invoke("get_test_metadata", { projectName, testName });
+}
+
+/**
+ * Update the description, tags or owner of a test
+ *
+ * @param projectName The name of the parent project
+ * @param testName The name of the test
+ * @param update The fields of the metadata to change
+ * @returns The metadata of the test after the update
+ */
+export async function updateTestMetadata(
+  projectName: string,
+  testName: string,
+  update: TestMetadataUpdate,
+): Promise<TestMetadata> {
+  return await invoke("update_test_metadata", { projectName, testName, update });
+}
+
+/**
+ * Run a script locally, in a new window.
+ *
+ * The environments of the project, if any, apply to the run. The status of
+ * the run is recorded in the metadata of the test, when the script is the
+ * one of a saved test of the project.
+ */
+export function runScriptLocally(
+  script: string,
+  projectName?: string,
+  testName?: string,
+): Promise<string> {
+  return invoke("open_run_window", { script, projectName, testName });
+}
+
+export type CloudRunStatus =
+  | "Initializing"
+  | "Running"
+  | "Finished"
+  | "ThresholdsFailed"
+  | "AbortedByThreshold"
+  | "AbortedByUser"
+  | "Failed";
+
+export interface CloudRun {
+  test_run_id: number;
+  url: string;
+}
+
+export interface CloudRunStatusEvent {
+  test_run_id: number;
+  status: CloudRunStatus;
 }
 
+/**
+ * Run a script in the cloud.
+ *
+ * The status of the run is then reported through `cloud-run-status` events
+ * until it completes.
+ *
+ * @returns The started cloud run
+ */
 export function runScriptInCloud({
   script,
+  projectName,
   projectId,
 }: {
   script: string;
-  projectId: string;
-}): Promise<string> {
-  return invoke("run_script_in_cloud", { script, projectId });
+  projectName?: string;
+  projectId?: string;
+}): Promise<CloudRun> {
+  return invoke("run_script_in_cloud", { script, projectName, projectId });
+}
+
+/**
+ * Stop a cloud test run started from kroco6.
+ *
+ * @param testRunId The id of the cloud test run to stop
+ * @param projectName The project whose credentials to use, for runs not started by this instance of the app
+ */
+export async function stopCloudTestRun(testRunId: number, projectName?: string): Promise<void> {
+  return await invoke("stop_cloud_test_run", { testRunId, projectName });
 }
 
 export async function getToken(): Promise<string> {
@@ -179,6 +520,302 @@ export async function saveProjectConfig(
   return await invoke("save_project_config", { projectName, projectConfig });
 }
 
+export interface CloudConfigValidation {
+  token_valid: boolean;
+  project_exists: boolean;
+  project_accessible: boolean;
+  project_name: string | null;
+  error: string | null;
+}
+
+/**
+ * Validate a cloud configuration against the cloud API before saving it.
+ *
+ * @param projectName The name of the project the configuration belongs to
+ * @param projectConfig The configuration to validate
+ * @returns The outcome of the validation
+ */
+export async function validateCloudConfig(
+  projectName: string,
+  projectConfig: ProjectConfig,
+): Promise<CloudConfigValidation> {
+  return await invoke("validate_cloud_config", {
+    projectName,
+    cloudToken: projectConfig.cloud_token,
+    cloudProjectId: projectConfig.cloud_project_id,
+  });
+}
+
 export async function getCloudTests(projectName: string): Promise<Array<CloudTest>> {
   return await invoke("get_cloud_tests", { projectName });
 }
+
+/**
+ * Export the flows captured by the recorder as a HAR file.
+ *
+ * @param path The path of the HAR file to write
+ */
+export async function exportRecordingHar(path: string): Promise<void> {
+  return await invoke("export_recording_har", { path });
+}
+
+export interface ScriptOptions {
+  // Leave requests fetching stylesheets, scripts, images, fonts... out of the script
+  filter_static_assets?: boolean;
+  // Replay the pauses made between pages as think time
+  think_time?: boolean;
+}
+
+/**
+ * Generate a k6 script out of the recorded flows, and save it as a new test.
+ *
+ * @param projectName The name of the project to create the test in
+ * @param testName The name of the test to create
+ * @param options The options of the script generation
+ * @returns The created test
+ */
+export async function generateScriptFromRecording(
+  projectName: string,
+  testName: string,
+  options: ScriptOptions | null = null,
+): Promise<Test> {
+  return await invoke("generate_script_from_recording", { projectName, testName, options });
+}
+
+export interface ImportedHar {
+  // The id of the recording the archive was saved as
+  recording_id: string;
+  // The flows of the archive, in the same shape as the ones emitted by the recorder
+  flows: Array<unknown>;
+  // The test created from the flows, if one was requested
+  test: Test | null;
+}
+
+/**
+ * Import the flows of a HAR file as a new recording, and optionally create a test replaying them.
+ *
+ * @param path The path of the HAR file to import
+ * @param projectName The name of the project to save the recording and create the test in
+ * @param testName The name of the test to create
+ * @returns The recording's id, the imported flows, and the created test if any
+ */
+export async function importHar(
+  path: string,
+  projectName: string,
+  testName: string | null = null,
+): Promise<ImportedHar> {
+  return await invoke("import_har", { path, projectName, testName });
+}
+
+// The filters applied by the recorder, host patterns are globs such as `*.example.com`
+export interface RecordingFilter {
+  allow_hosts?: string[];
+  deny_hosts?: string[];
+  deny_content_types?: string[];
+  methods?: string[];
+}
+
+export interface DetectedBrowser {
+  name: string;
+  path: string;
+}
+
+/**
+ * List the Chromium-family browsers the recorder can use
+ *
+ * @returns The browsers detected on the machine
+ */
+export async function listBrowsers(): Promise<DetectedBrowser[]> {
+  return await invoke("list_browsers", {});
+}
+
+export interface RecordingSummary {
+  id: string;
+  started_at: string;
+  flow_count: number;
+}
+
+// A named boundary added while recording, which starts a new group in the generated script
+export interface RecordingMarker {
+  label: string;
+  // Unix timestamp in seconds
+  timestamp: number;
+}
+
+export interface RecordingSession {
+  id: string;
+  started_at: string;
+  flows: Array<unknown>;
+  markers: RecordingMarker[];
+}
+
+/**
+ * Pause the current recorder session, requests made while paused are not recorded
+ */
+export async function pauseRecording(): Promise<void> {
+  return await invoke("pause_recording", {});
+}
+
+/**
+ * Resume recording after a pause
+ */
+export async function resumeRecording(): Promise<void> {
+  return await invoke("resume_recording", {});
+}
+
+/**
+ * Add a marker to the current recorder session, grouping the following requests under its label
+ *
+ * @param label The label of the marker, e.g. "login" or "checkout"
+ * @returns The added marker
+ */
+export async function addRecordingMarker(label: string): Promise<RecordingMarker> {
+  return await invoke("add_recording_marker", { label });
+}
+
+/**
+ * List the recorder sessions saved in a project
+ *
+ * @param projectName The name of the project
+ * @returns The summaries of the saved sessions, most recent first
+ */
+export async function listRecordings(projectName: string): Promise<RecordingSummary[]> {
+  return await invoke("list_recordings", { projectName });
+}
+
+/**
+ * Load a recorder session saved in a project, making it the current one
+ *
+ * @param projectName The name of the project
+ * @param recordingId The id of the session
+ * @returns The saved session
+ */
+export async function loadRecording(
+  projectName: string,
+  recordingId: string,
+): Promise<RecordingSession> {
+  return await invoke("load_recording", { projectName, recordingId });
+}
+
+/**
+ * Delete a recorder session saved in a project
+ *
+ * @param projectName The name of the project
+ * @param recordingId The id of the session
+ */
+export async function deleteRecording(projectName: string, recordingId: string): Promise<void> {
+  return await invoke("delete_recording", { projectName, recordingId });
+}
+
+// An ordered list of tests of a project, ran together
+export interface Suite {
+  name: string;
+  mode?: SuiteMode;
+  steps: SuiteStep[];
+}
+
+export type SuiteMode = "Sequential" | "Parallel";
+
+export interface SuiteStep {
+  test_name: string;
+  options?: StepOptions;
+}
+
+export interface StepOptions {
+  // The environment the test runs in, instead of the active one
+  environment?: string;
+  vus?: number;
+  duration?: string;
+  iterations?: number;
+  // Whether the next steps of a sequential suite still run when this one fails
+  continue_on_failure?: boolean;
+}
+
+export type StepStatus = "Passed" | "Failed" | "Error" | "Skipped";
+
+export interface StepResult {
+  test_name: string;
+  status: StepStatus;
+  exit_code: number | null;
+  // In seconds
+  duration: number;
+  output: string;
+  error: string | null;
+}
+
+export interface SuiteResult {
+  suite_name: string;
+  started_at: string;
+  finished_at: string;
+  passed: boolean;
+  passed_count: number;
+  failed_count: number;
+  skipped_count: number;
+  steps: StepResult[];
+}
+
+// The payload of the `suite-step-finished` event
+export interface SuiteStepEvent {
+  suite_name: string;
+  index: number;
+  result: StepResult;
+}
+
+/**
+ * List the suites of a project
+ *
+ * @param projectName The name of the project
+ * @returns The suites, sorted by name
+ */
+export async function listSuites(projectName: string): Promise<Suite[]> {
+  return await invoke("list_suites", { projectName });
+}
+
+/**
+ * Get a suite of a project
+ *
+ * @param projectName The name of the project
+ * @param suiteName The name of the suite
+ * @returns The suite
+ */
+export async function getSuite(projectName: string, suiteName: string): Promise<Suite> {
+  return await invoke("get_suite", { projectName, suiteName });
+}
+
+/**
+ * Save a suite in a project, replacing the one with the same name if any
+ *
+ * @param projectName The name of the project
+ * @param suite The suite, whose steps must reference tests of the project
+ */
+export async function saveSuite(projectName: string, suite: Suite): Promise<void> {
+  return await invoke("save_suite", { projectName, suite });
+}
+
+/**
+ * Delete a suite of a project
+ *
+ * @param projectName The name of the project
+ * @param suiteName The name of the suite
+ */
+export async function deleteSuite(projectName: string, suiteName: string): Promise<void> {
+  return await invoke("delete_suite", { projectName, suiteName });
+}
+
+/**
+ * Run the tests of a suite locally, a `suite-step-finished` event being
+ * emitted as each of them finishes
+ *
+ * @param projectName The name of the project
+ * @param suiteName The name of the suite
+ * @param scripts The scripts to run for the tests which aren't javascript ones, by test
+ * name, e.g. the ones generated from blocks tests with `convertToScript`
+ * @returns The aggregated result of the suite
+ */
+export async function runSuite(
+  projectName: string,
+  suiteName: string,
+  scripts: Record<string, string> | null = null,
+): Promise<SuiteResult> {
+  return await invoke("run_suite", { projectName, suiteName, scripts });
+}
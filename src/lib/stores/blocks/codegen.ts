@@ -1,11 +1,11 @@
-import type { Environment } from "$lib/backend-client";
+import type { Environment, EnvironmentVariable } from "$lib/backend-client";
 import * as prettier from "prettier";
 import * as babelParser from "prettier/parser-babel";
 import * as estreePlugin from "prettier/plugins/estree";
 import type { Executor, Scenario, Step, Test } from "../../types";
 import { exhaustive } from "../../utils/typescript";
 
-type Substitution = [name: string, value: string];
+type Substitution = [name: string, variable: EnvironmentVariable];
 
 function sanitizeName(name: string) {
   const parts = name
@@ -25,18 +25,51 @@ function sanitizeName(name: string) {
   return sanitizied;
 }
 
-function substitute(target: string, substitutions: Substitution[]) {
-  return substitutions.reduce((acc, [name, value]) => acc.replaceAll(`{{${name}}}`, value), target);
+// Returns a javascript expression evaluating to the target string, in which
+// the `{{name}}` references to variables are substituted. Secret values are
+// only known to k6 at runtime, so they are read from `__ENV` instead.
+function emitString(target: string, substitutions: Substitution[]) {
+  const variables = new Map(substitutions);
+  const parts: string[] = [];
+
+  let literal = "";
+  let last = 0;
+  for (const match of target.matchAll(/\{\{(.+?)\}\}/g)) {
+    const variable = variables.get(match[1] ?? "");
+    if (variable === undefined) {
+      continue;
+    }
+
+    literal += target.slice(last, match.index);
+    last = (match.index ?? 0) + match[0].length;
+
+    if (variable.secret) {
+      if (literal !== "") {
+        parts.push(JSON.stringify(literal));
+      }
+      parts.push(`__ENV[${JSON.stringify(match[1])}]`);
+      literal = "";
+    } else {
+      literal += variable.value;
+    }
+  }
+
+  literal += target.slice(last);
+  if (literal !== "" || parts.length === 0) {
+    parts.push(JSON.stringify(literal));
+  }
+
+  return parts.join(" + ");
 }
 
 function emitStep(step: Step, substitutions: Substitution[]): string {
   switch (step.type) {
     case "http-request":
-      return `http.${step.method.toLowerCase()}(${substitute(JSON.stringify(step.url), substitutions)});`;
+      return `http.${step.method.toLowerCase()}(${emitString(step.url, substitutions)});`;
 
     case "group":
       return `
-        group("${substitute(step.name, substitutions)}", () => {
+        group(${emitString(step.name, substitutions)}, () => {
           ${step.steps.map((step) => emitStep(step, substitutions)).join("\n\n")}
         }); 
       `;
@@ -53,7 +86,7 @@ function emitStep(step: Step, substitutions: Substitution[]): string {
                   return `status: (r) => r.status === ${check.status}`;
 
                 case "body-contains":
-                  return `body: (r) => r.body.includes(${JSON.stringify(substitute(check.value, substitutions))})`;
+                  return `body: (r) => r.body.includes(${emitString(check.value, substitutions)})`;
               }
             })
             .join(",\n")},
@@ -75,7 +108,7 @@ function emitExecutor(exec: string, executor: Executor, substitutions: Substitut
         { 
           "executor": "constant-vus", 
           "vus": ${executor.vus}, 
-          "duration": "${substitute(executor.duration, substitutions)}",
+          "duration": ${emitString(executor.duration, substitutions)},
           "exec": "${exec}",
         }
       `;
@@ -108,7 +141,8 @@ function emitScenarioOptions(scenario: Scenario, substitutions: Substitution[])
 }
 
 function emitScenario(scenario: Scenario, substitutions: Substitution[]) {
-  const scenarioName = sanitizeName(substitute(scenario.name, substitutions));
+  // the name of the scenario is used as is, it has to match the one in the options
+  const scenarioName = sanitizeName(scenario.name);
 
   return `  
     export function ${scenarioName}() {
@@ -120,7 +154,7 @@ function emitScenario(scenario: Scenario, substitutions: Substitution[]) {
 }
 
 function emitScript(env: Environment, test: Test) {
-  const substitutions = Object.entries(env.variables);
+  const substitutions: Substitution[] = Object.entries(env.variables);
 
   const scenarioOptions = test.scenarios.flatMap((scenario) =>
     emitScenarioOptions(scenario, substitutions),